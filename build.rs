@@ -0,0 +1,4 @@
+// Embedded migrations are only picked up on rebuild
+fn main() {
+    println!("cargo:rerun-if-changed=db/migrations");
+}
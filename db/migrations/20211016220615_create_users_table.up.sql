@@ -1,4 +1,3 @@
--- migrate:up
 CREATE TABLE users
 ( 
   id SERIAL PRIMARY KEY,
@@ -10,6 +9,3 @@ CREATE TABLE users
   created_at TIMESTAMPTZ NOT NULL,
   deleted_at TIMESTAMPTZ
 );
-
--- migrate:down
-DROP TABLE IF EXISTS users;
\ No newline at end of file
@@ -1,4 +1,3 @@
--- migrate:up
 CREATE TABLE notes 
 ( 
   id SERIAL PRIMARY KEY,
@@ -11,6 +10,3 @@ CREATE TABLE notes
   key text NOT NULL,
   content text NOT NULL
 );
-
--- migrate:down
-DROP TABLE IF EXISTS notes;
\ No newline at end of file
@@ -1,4 +1,3 @@
--- migrate:up
 CREATE TABLE auth_tokens 
 ( 
   id SERIAL PRIMARY KEY,
@@ -6,6 +5,3 @@ CREATE TABLE auth_tokens
   token text NOT NULL UNIQUE,
   created_at TIMESTAMPTZ NOT NULL
 );
-
--- migrate:down
-DROP TABLE IF EXISTS auth_tokens;
@@ -1,4 +1,3 @@
--- migrate:up
 CREATE TABLE shares 
 ( 
   id SERIAL PRIMARY KEY,
@@ -10,6 +9,3 @@ CREATE TABLE shares
   view_count integer NOT NULL,
   public text
 );
-
--- migrate:down
-DROP TABLE IF EXISTS shares;
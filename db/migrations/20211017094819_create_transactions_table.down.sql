@@ -1,5 +1,2 @@
--- migrate:up
 DROP TABLE IF EXISTS transactions;
-
--- migrate:down
-
+DROP TYPE IF EXISTS event;
@@ -1,4 +1,3 @@
--- migrate:up
 CREATE TYPE event AS ENUM ('startfieldnotes', 'pausefieldnotes', 'addfunds');
 
 CREATE TABLE transactions
@@ -9,7 +8,3 @@ CREATE TABLE transactions
   amount BIGINT,
   date TIMESTAMPTZ NOT NULL
 );
-
--- migrate:down
-DROP TABLE IF EXISTS app_events;
-DROP TYPE IF EXISTS event;
\ No newline at end of file
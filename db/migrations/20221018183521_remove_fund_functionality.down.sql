@@ -0,0 +1,8 @@
+CREATE TABLE transactions
+( 
+  id SERIAL PRIMARY KEY,
+  user_id integer NOT NULL REFERENCES users(id),
+  event event NOT NULL,
+  amount BIGINT,
+  date TIMESTAMPTZ NOT NULL
+);
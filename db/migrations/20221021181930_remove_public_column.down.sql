@@ -0,0 +1,2 @@
+ALTER TABLE shares
+ADD COLUMN public text;
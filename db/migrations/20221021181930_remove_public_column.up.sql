@@ -1,6 +1,2 @@
--- migrate:up
 ALTER TABLE shares
 DROP COLUMN public;
-
--- migrate:down
-
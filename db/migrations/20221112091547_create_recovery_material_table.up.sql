@@ -0,0 +1,9 @@
+CREATE TABLE recovery_material
+( 
+  id SERIAL PRIMARY KEY,
+  user_id integer NOT NULL REFERENCES users(id),
+  version integer NOT NULL,
+  material text NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  UNIQUE (user_id, version)
+);
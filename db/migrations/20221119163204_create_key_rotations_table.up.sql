@@ -0,0 +1,15 @@
+CREATE TABLE key_rotations
+( 
+  id SERIAL PRIMARY KEY,
+  user_id integer NOT NULL REFERENCES users(id) UNIQUE,
+  started_at TIMESTAMPTZ NOT NULL,
+  expires_at TIMESTAMPTZ NOT NULL
+);
+
+CREATE TABLE key_rotation_notes
+( 
+  rotation_id integer NOT NULL REFERENCES key_rotations(id) ON DELETE CASCADE,
+  note_id integer NOT NULL REFERENCES notes(id) ON DELETE CASCADE,
+  key text NOT NULL,
+  PRIMARY KEY (rotation_id, note_id)
+);
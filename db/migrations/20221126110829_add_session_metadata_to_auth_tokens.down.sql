@@ -0,0 +1,4 @@
+ALTER TABLE auth_tokens
+DROP COLUMN last_used_at,
+DROP COLUMN user_agent,
+DROP COLUMN device_name;
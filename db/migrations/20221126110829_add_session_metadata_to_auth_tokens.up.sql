@@ -0,0 +1,10 @@
+ALTER TABLE auth_tokens
+ADD COLUMN last_used_at TIMESTAMPTZ,
+ADD COLUMN user_agent text,
+ADD COLUMN device_name text;
+
+UPDATE auth_tokens
+SET last_used_at = created_at;
+
+ALTER TABLE auth_tokens
+ALTER COLUMN last_used_at SET NOT NULL;
@@ -0,0 +1,2 @@
+ALTER TABLE auth_tokens
+ADD COLUMN rotated_at TIMESTAMPTZ;
@@ -0,0 +1 @@
+DELETE FROM auth_tokens;
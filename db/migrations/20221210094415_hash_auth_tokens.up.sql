@@ -0,0 +1,2 @@
+UPDATE auth_tokens
+SET token = encode(sha256(token::bytea), 'hex');
@@ -0,0 +1,11 @@
+CREATE TABLE api_tokens
+( 
+  id SERIAL PRIMARY KEY,
+  user_id integer NOT NULL REFERENCES users(id),
+  name text NOT NULL,
+  token text NOT NULL UNIQUE,
+  scopes text[] NOT NULL,
+  created_at TIMESTAMPTZ NOT NULL,
+  expires_at TIMESTAMPTZ,
+  last_used_at TIMESTAMPTZ
+);
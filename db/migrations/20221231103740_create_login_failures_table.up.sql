@@ -0,0 +1,7 @@
+CREATE TABLE login_failures
+( 
+  key text PRIMARY KEY,
+  failures integer NOT NULL,
+  last_failure_at TIMESTAMPTZ NOT NULL,
+  locked_until TIMESTAMPTZ
+);
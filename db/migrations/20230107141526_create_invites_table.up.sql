@@ -0,0 +1,10 @@
+CREATE TABLE invites
+( 
+  id SERIAL PRIMARY KEY,
+  code text NOT NULL UNIQUE,
+  created_by integer NOT NULL REFERENCES users(id),
+  created_at TIMESTAMPTZ NOT NULL,
+  expires_at TIMESTAMPTZ,
+  max_uses integer NOT NULL CHECK (max_uses > 0),
+  uses integer NOT NULL DEFAULT 0
+);
@@ -0,0 +1,8 @@
+ALTER TABLE auth_tokens
+ADD COLUMN session_started_at TIMESTAMPTZ;
+
+UPDATE auth_tokens
+SET session_started_at = created_at;
+
+ALTER TABLE auth_tokens
+ALTER COLUMN session_started_at SET NOT NULL;
@@ -0,0 +1,43 @@
+use axum::{extract::Extension, http::StatusCode};
+use sqlx::PgPool;
+use tracing::warn;
+
+use crate::{
+    authentication::AuthenticatedAdmin, error::AppError, extract::Path, users::delete_all_user_data,
+};
+
+/// Delete an account with all associated data
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the user")),
+    responses(
+        (status = 200, description = "User and all their data deleted"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "User doesn't exist", body = ErrorResponse),
+        (status = 409, description = "Admins can't delete themselves", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn admin_delete_user_handler(
+    Path(id): Path<i32>,
+    admin: AuthenticatedAdmin,
+    db: Extension<PgPool>,
+) -> Result<StatusCode, AppError> {
+    if id == admin.user_id {
+        return Err(AppError::Conflict {
+            code: "cannot_delete_self",
+            message: "Admins can't delete their own account",
+        });
+    }
+
+    if !delete_all_user_data(id, &db).await? {
+        return Err(AppError::NotFound("User"));
+    }
+
+    warn!("User {} deleted by admin {}", id, admin.user_id);
+
+    Ok(StatusCode::OK)
+}
@@ -0,0 +1,99 @@
+use axum::extract::Extension;
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use sqlx::{query, PgPool};
+use tracing::warn;
+
+use crate::{
+    authentication::{delete_all_api_tokens, delete_all_auth_tokens, AuthenticatedAdmin},
+    error::AppError,
+    extract::Path,
+};
+
+/// Disable an account, logging out all its sessions and revoking its API tokens
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/disable",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the user")),
+    responses(
+        (status = 200, description = "User disabled and logged out"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "User doesn't exist", body = ErrorResponse),
+        (status = 409, description = "Admins can't disable themselves", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn disable_user_handler(
+    Path(id): Path<i32>,
+    admin: AuthenticatedAdmin,
+    db: Extension<PgPool>,
+) -> Result<StatusCode, AppError> {
+    if id == admin.user_id {
+        return Err(AppError::Conflict {
+            code: "cannot_disable_self",
+            message: "Admins can't disable their own account",
+        });
+    }
+
+    let now = Utc::now();
+
+    if !set_disabled_at(id, Some(now), &db).await? {
+        return Err(AppError::NotFound("User"));
+    }
+
+    delete_all_auth_tokens(id, &db).await?;
+    delete_all_api_tokens(id, &db).await?;
+
+    warn!("User {} disabled by admin {}", id, admin.user_id);
+
+    Ok(StatusCode::OK)
+}
+
+/// Enable a previously disabled account
+#[utoipa::path(
+    post,
+    path = "/admin/users/{id}/enable",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the user")),
+    responses(
+        (status = 200, description = "User enabled"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "User doesn't exist", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn enable_user_handler(
+    Path(id): Path<i32>,
+    admin: AuthenticatedAdmin,
+    db: Extension<PgPool>,
+) -> Result<StatusCode, AppError> {
+    if !set_disabled_at(id, None, &db).await? {
+        return Err(AppError::NotFound("User"));
+    }
+
+    warn!("User {} enabled by admin {}", id, admin.user_id);
+
+    Ok(StatusCode::OK)
+}
+
+/// Set or clear the disabled mark of a user, returns false if the user doesn't exist
+pub async fn set_disabled_at(
+    user_id: i32,
+    disabled_at: Option<DateTime<Utc>>,
+    db: &PgPool,
+) -> Result<bool, AppError> {
+    let result = query!(
+        "UPDATE users
+        SET disabled_at = $1
+        WHERE id = $2;",
+        disabled_at,
+        user_id,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
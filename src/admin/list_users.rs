@@ -0,0 +1,77 @@
+use crate::{authentication::AuthenticatedAdmin, error::AppError};
+use axum::{extract::Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+/// User overview for administrators
+#[derive(Serialize, ToSchema)]
+pub struct ListUserResponse {
+    pub id: i32,
+    pub username: String,
+    pub email: Option<String>,
+    pub role: String,
+    pub created_at: DateTime<Utc>,
+    pub disabled_at: Option<DateTime<Utc>>,
+    pub deleted_at: Option<DateTime<Utc>>,
+    pub last_active_at: Option<DateTime<Utc>>,
+    pub notes: i64,
+    pub shares: i64,
+    pub storage_bytes: i64,
+}
+
+/// List all users with their storage usage
+#[utoipa::path(
+    get,
+    path = "/admin/users",
+    tag = "admin",
+    responses(
+        (status = 200, description = "All users", body = [ListUserResponse]),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn list_users_handler(
+    _admin: AuthenticatedAdmin,
+    db: Extension<PgPool>,
+) -> Result<Json<Vec<ListUserResponse>>, AppError> {
+    let users = list_users(&db).await?;
+
+    Ok(Json(users))
+}
+
+/// All users with their storage usage, ordered by id
+pub async fn list_users(db: &PgPool) -> Result<Vec<ListUserResponse>, AppError> {
+    let rows = query!(
+        r#"SELECT users.id, users.username, users.email, users.role, users.created_at,
+            users.disabled_at, users.deleted_at,
+            (SELECT MAX(last_used_at) FROM auth_tokens WHERE user_id = users.id) AS last_active_at,
+            (SELECT COUNT(*) FROM notes WHERE user_id = users.id) AS "notes!",
+            (SELECT COUNT(*) FROM shares WHERE user_id = users.id) AS "shares!",
+            (SELECT COALESCE(SUM(octet_length(metadata) + octet_length(key) + octet_length(content)), 0)
+                FROM notes WHERE user_id = users.id)::bigint AS "storage_bytes!"
+        FROM users
+        ORDER BY users.id;"#
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|user| ListUserResponse {
+            id: user.id,
+            username: user.username,
+            email: user.email,
+            role: user.role,
+            created_at: user.created_at,
+            disabled_at: user.disabled_at,
+            deleted_at: user.deleted_at,
+            last_active_at: user.last_active_at,
+            notes: user.notes,
+            shares: user.shares,
+            storage_bytes: user.storage_bytes,
+        })
+        .collect())
+}
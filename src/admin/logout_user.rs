@@ -0,0 +1,50 @@
+use axum::{extract::Extension, http::StatusCode};
+use sqlx::{query, PgPool};
+use tracing::warn;
+
+use crate::{
+    authentication::{delete_all_auth_tokens, AuthenticatedAdmin},
+    error::AppError,
+    extract::Path,
+};
+
+/// Log out all sessions of a user
+#[utoipa::path(
+    delete,
+    path = "/admin/users/{id}/sessions",
+    tag = "admin",
+    params(("id" = i32, Path, description = "Id of the user")),
+    responses(
+        (status = 200, description = "All sessions of the user revoked"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 403, description = "Not an admin", body = ErrorResponse),
+        (status = 404, description = "User doesn't exist", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn logout_user_handler(
+    Path(id): Path<i32>,
+    admin: AuthenticatedAdmin,
+    db: Extension<PgPool>,
+) -> Result<StatusCode, AppError> {
+    if !user_id_exists(id, &db).await? {
+        return Err(AppError::NotFound("User"));
+    }
+
+    delete_all_auth_tokens(id, &db).await?;
+
+    warn!("Sessions of user {} revoked by admin {}", id, admin.user_id);
+
+    Ok(StatusCode::OK)
+}
+
+async fn user_id_exists(user_id: i32, db: &PgPool) -> Result<bool, AppError> {
+    let row = query!(
+        r#"SELECT EXISTS(SELECT 1 FROM users WHERE id = $1) AS "exists!";"#,
+        user_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.exists)
+}
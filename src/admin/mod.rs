@@ -0,0 +1,25 @@
+mod delete_user;
+mod disable_user;
+mod list_users;
+mod logout_user;
+
+pub use delete_user::admin_delete_user_handler;
+pub use disable_user::{disable_user_handler, enable_user_handler, set_disabled_at};
+pub use list_users::{list_users, list_users_handler};
+pub use logout_user::logout_user_handler;
+
+use utoipa::OpenApi;
+
+/// OpenAPI document of the administration routes
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list_users::list_users_handler,
+        delete_user::admin_delete_user_handler,
+        disable_user::disable_user_handler,
+        disable_user::enable_user_handler,
+        logout_user::logout_user_handler,
+    ),
+    components(schemas(list_users::ListUserResponse,))
+)]
+pub struct AdminApi;
@@ -0,0 +1,86 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{query, PgPool};
+use tracing::info;
+
+use crate::{error::AppError, util::hash_token};
+
+use super::{AuthenticatedUser, Credential, Scope, TOKEN_TOUCH_INTERVAL_MINUTES};
+
+// Checks if API token is valid and grants the scope required by the route
+pub async fn is_authorized_with_api_token(
+    token: &str,
+    required_scope: Option<Scope>,
+    db: &PgPool,
+) -> Result<AuthenticatedUser, AppError> {
+    let row = match query!(
+        "SELECT api_tokens.id, api_tokens.scopes, api_tokens.expires_at, api_tokens.last_used_at,
+            users.id AS user_id, users.username
+        FROM api_tokens
+        INNER JOIN users ON users.id = api_tokens.user_id
+        WHERE api_tokens.token = $1 AND users.disabled_at IS NULL;",
+        hash_token(token)
+    )
+    .fetch_optional(db)
+    .await?
+    {
+        Some(row) => row,
+        None => return Err(AppError::Unauthorized),
+    };
+
+    let now = Utc::now();
+
+    if let Some(expires_at) = row.expires_at {
+        if expires_at < now {
+            return Err(AppError::Unauthorized);
+        }
+    }
+
+    info!("Access with API token: {}", row.id);
+
+    // Routes without a required scope are only accessible with a session
+    let granted = match required_scope {
+        Some(scope) => row.scopes.iter().any(|name| name == scope.as_str()),
+        None => false,
+    };
+
+    if !granted {
+        return Err(AppError::Forbidden {
+            code: "insufficient_scope",
+            message: "API token lacks the scope required by this route",
+        });
+    }
+
+    let touch_due = match row.last_used_at {
+        Some(last_used_at) => last_used_at + Duration::minutes(TOKEN_TOUCH_INTERVAL_MINUTES) < now,
+        None => true,
+    };
+
+    if touch_due {
+        touch_api_token(row.id, now, db).await?;
+    }
+
+    Ok(AuthenticatedUser {
+        user_id: row.user_id,
+        credential: Credential::ApiToken(row.id),
+        username: row.username,
+    })
+}
+
+// Record last usage of API token
+async fn touch_api_token(
+    token_id: i32,
+    last_used_at: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<(), AppError> {
+    query!(
+        "UPDATE api_tokens
+        SET last_used_at = $1
+        WHERE id = $2;",
+        last_used_at,
+        token_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -1,22 +1,43 @@
+mod api_token;
+mod scope;
+mod session;
+mod throttle;
+
+pub use scope::Scope;
+pub use session::{session_renewal, SessionPolicy, SessionRenewal};
+pub use throttle::{LoginThrottle, FAILURE_RETENTION_HOURS};
+
 use crate::{
+    config::SharedConfig,
     error::AppError,
-    util::{get_token_from_header, truncate_auth_token},
+    metrics::Metrics,
+    request_id::record_user,
+    util::{get_auth_token, get_bearer_token, get_token_from_header, hash_token, API_TOKEN_PREFIX},
 };
 use axum::{
     async_trait,
     extract::{Extension, FromRequest, RequestParts},
 };
 use chrono::{DateTime, Duration, Utc};
-use log::info;
 use sqlx::{query, PgPool, Pool, Postgres};
+use tracing::info;
+
+use self::api_token::is_authorized_with_api_token;
+
+/// Minimum time between updates of the last usage of a token: 5 minutes
+const TOKEN_TOUCH_INTERVAL_MINUTES: i64 = 5;
 
-/// Token expiration time: 2 months
-pub const TOKEN_EXPIRATION_WEEKS: i64 = 8;
+/// Time a rotated token stays valid for requests already in flight: 1 minute
+pub const TOKEN_ROTATION_GRACE_SECONDS: i64 = 60;
+
+/// Role of users allowed to use the administration API
+pub const ADMIN_ROLE: &str = "admin";
+
+/// Role of all other users
+pub const USER_ROLE: &str = "user";
 
 pub struct AuthenticatedFundedUser {
     pub user_id: i32,
-    pub auth_token: String,
-    pub username: String,
 }
 
 #[async_trait]
@@ -27,27 +48,94 @@ where
     type Rejection = AppError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let user = authenticate(req).await?;
+
+        Ok(AuthenticatedFundedUser {
+            user_id: user.user_id,
+        })
+    }
+}
+
+/// Authenticated user with the admin role. Only session tokens grant admin access.
+pub struct AuthenticatedAdmin {
+    pub user_id: i32,
+}
+
+#[async_trait]
+impl<B> FromRequest<B> for AuthenticatedAdmin
+where
+    B: Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let user = authenticate(req).await?;
+
+        if user.session_id().is_none() {
+            return Err(AppError::Forbidden {
+                code: "session_required",
+                message: "Route is only accessible with a session",
+            });
+        }
+
         let Extension(db) = Extension::<Pool<Postgres>>::from_request(req)
             .await
             .expect("db missing");
 
-        let token = get_token_from_header(req.headers())?;
-
-        let user = is_authorized_with_user(token, &db).await?;
+        if !is_admin(&user, &db).await? {
+            return Err(AppError::Forbidden {
+                code: "admin_required",
+                message: "Route is only accessible to admins",
+            });
+        }
 
-        Ok(AuthenticatedFundedUser {
+        Ok(AuthenticatedAdmin {
             user_id: user.user_id,
-            auth_token: user.auth_token,
-            username: user.username,
         })
     }
 }
+
+/// Whether the user has the admin role and authenticated with a session
+pub async fn is_admin(user: &AuthenticatedUser, db: &PgPool) -> Result<bool, AppError> {
+    if user.session_id().is_none() {
+        return Ok(false);
+    }
+
+    let row = query!(
+        "SELECT role
+        FROM users
+        WHERE id = $1;",
+        user.user_id
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.role == ADMIN_ROLE)
+}
+
 pub struct AuthenticatedUser {
     pub user_id: i32,
-    pub auth_token: String,
+    pub credential: Credential,
     pub username: String,
 }
 
+/// Credential a request was authenticated with
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum Credential {
+    Session(i32),
+    ApiToken(i32),
+}
+
+impl AuthenticatedUser {
+    /// Id of the session, if authenticated with a session token
+    pub fn session_id(&self) -> Option<i32> {
+        match self.credential {
+            Credential::Session(session_id) => Some(session_id),
+            Credential::ApiToken(_) => None,
+        }
+    }
+}
+
 #[async_trait]
 impl<B> FromRequest<B> for AuthenticatedUser
 where
@@ -56,76 +144,225 @@ where
     type Rejection = AppError;
 
     async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
-        let Extension(db) = Extension::<Pool<Postgres>>::from_request(req)
-            .await
-            .expect("db missing");
+        authenticate(req).await
+    }
+}
 
-        let token = get_token_from_header(req.headers())?;
+// Authenticate request, recording the user on the request span and counting failed
+// attempts by kind of credential
+async fn authenticate<B: Send>(req: &mut RequestParts<B>) -> Result<AuthenticatedUser, AppError> {
+    let result = authenticate_credential(req).await;
 
-        let user = is_authorized_with_user(token, &db).await?;
+    if let Ok(user) = &result {
+        record_user(user.user_id);
+    }
+
+    if let Err(AppError::Unauthorized) = result {
+        if let Some(metrics) = req.extensions().get::<Metrics>() {
+            let is_api_token = get_bearer_token(req.headers())
+                .map(|token| token.starts_with(API_TOKEN_PREFIX))
+                .unwrap_or(false);
 
-        Ok(user)
+            metrics.auth_failure(if is_api_token { "api_token" } else { "session" });
+        }
     }
+
+    result
+}
+
+// Authenticate request by API token or session token, rotating the session token if it is
+// about to expire
+async fn authenticate_credential<B: Send>(
+    req: &mut RequestParts<B>,
+) -> Result<AuthenticatedUser, AppError> {
+    let Extension(db) = Extension::<Pool<Postgres>>::from_request(req)
+        .await
+        .expect("db missing");
+
+    if let Some(token) = get_bearer_token(req.headers()) {
+        if token.starts_with(API_TOKEN_PREFIX) {
+            let required_scope = req.extensions().get::<Scope>().copied();
+
+            return is_authorized_with_api_token(&token, required_scope, &db).await;
+        }
+    }
+
+    let Extension(config) = Extension::<SharedConfig>::from_request(req)
+        .await
+        .expect("config missing");
+    let policy = &config.session;
+
+    let token = get_token_from_header(req.headers(), &config.cookie)?;
+
+    let info = is_authorized_with_user(token, policy, &db).await?;
+
+    let now = Utc::now();
+    let expires_at = info.session_started_at + policy.lifetime;
+    let renewal_starts_at = expires_at - policy.renewal_window;
+
+    // Tokens issued before the renewal window of their session are replaced once it starts
+    if info.created_at < renewal_starts_at && renewal_starts_at < now {
+        if let Some(renewal) = req.extensions().get::<SessionRenewal>() {
+            if let Some(token) = rotate_auth_token(info.session_id, now, &db).await? {
+                renewal.set(token, expires_at - now);
+            }
+        }
+    }
+
+    Ok(info.user)
 }
 
 // Checks if user has proper authorization token for request and return user id
 // used in further filters and handlers.
-pub async fn is_authorized_with_user(
+async fn is_authorized_with_user(
     token: String,
+    policy: &SessionPolicy,
     db: &PgPool,
-) -> Result<AuthenticatedUser, AppError> {
-    let (authorized_user, created_at) = get_auth_token_info(&token, db).await?;
+) -> Result<AuthTokenInfo, AppError> {
+    let info = get_auth_token_info(&hash_token(&token), db).await?;
 
     let now = Utc::now();
 
-    info!("Access with token: {}", truncate_auth_token(&token));
+    info!("Access with session: {}", info.session_id);
+
+    let rotation_expired = match info.rotated_at {
+        Some(rotated_at) => rotated_at + Duration::seconds(TOKEN_ROTATION_GRACE_SECONDS) < now,
+        None => false,
+    };
 
-    if created_at + Duration::weeks(TOKEN_EXPIRATION_WEEKS) > now {
-        Ok(authorized_user)
-    } else {
-        Err(AppError::Unauthorized)
+    if rotation_expired
+        || info.session_started_at + policy.lifetime < now
+        || info.last_used_at + policy.idle_timeout < now
+    {
+        return Err(AppError::Unauthorized);
     }
+
+    if info.last_used_at + Duration::minutes(TOKEN_TOUCH_INTERVAL_MINUTES) < now {
+        touch_auth_token(info.session_id, now, db).await?;
+    }
+
+    Ok(info)
 }
 
-// Get user_id and creation date of provided token
-async fn get_auth_token_info(
-    token: &str,
-    db: &PgPool,
-) -> Result<(AuthenticatedUser, DateTime<Utc>), AppError> {
+struct AuthTokenInfo {
+    user: AuthenticatedUser,
+    session_id: i32,
+    created_at: DateTime<Utc>,
+    session_started_at: DateTime<Utc>,
+    last_used_at: DateTime<Utc>,
+    rotated_at: Option<DateTime<Utc>>,
+}
+
+// Get user and usage dates of token with provided digest
+async fn get_auth_token_info(token_hash: &str, db: &PgPool) -> Result<AuthTokenInfo, AppError> {
     match query!(
-        "SELECT users.id, users.username, auth_tokens.token, auth_tokens.created_at
+        "SELECT users.id, users.username, auth_tokens.id AS session_id,
+            auth_tokens.created_at, auth_tokens.session_started_at, auth_tokens.last_used_at,
+            auth_tokens.rotated_at
         FROM auth_tokens 
         INNER JOIN users ON users.id = auth_tokens.user_id
-        WHERE auth_tokens.token = $1;",
-        token
+        WHERE auth_tokens.token = $1 AND users.disabled_at IS NULL;",
+        token_hash
     )
     .fetch_optional(db)
     .await?
     {
-        Some(tok) => Ok((
-            AuthenticatedUser {
+        Some(tok) => Ok(AuthTokenInfo {
+            user: AuthenticatedUser {
                 user_id: tok.id,
-                auth_token: tok.token,
+                credential: Credential::Session(tok.session_id),
                 username: tok.username,
             },
-            tok.created_at,
-        )),
+            session_id: tok.session_id,
+            created_at: tok.created_at,
+            session_started_at: tok.session_started_at,
+            last_used_at: tok.last_used_at,
+            rotated_at: tok.rotated_at,
+        }),
         None => Err(AppError::Unauthorized),
     }
 }
 
-/// Add a new token to the user. User is expected to exist.
+// Replace token by a new one for the same session, which keeps its start and thereby its
+// lifetime. Returns None if the token was already rotated by a concurrent request.
+async fn rotate_auth_token(
+    session_id: i32,
+    now: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<Option<String>, AppError> {
+    let mut tx = db.begin().await?;
+
+    let result = query!(
+        "UPDATE auth_tokens
+        SET rotated_at = $1
+        WHERE id = $2 AND rotated_at IS NULL;",
+        now,
+        session_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    if result.rows_affected() != 1 {
+        tx.rollback().await?;
+        return Ok(None);
+    }
+
+    let token = get_auth_token();
+
+    query!(
+        "INSERT INTO auth_tokens
+            (token, created_at, session_started_at, last_used_at, user_agent, device_name, user_id)
+        SELECT $1, $2, session_started_at, $2, user_agent, device_name, user_id
+        FROM auth_tokens
+        WHERE id = $3;",
+        hash_token(&token),
+        now,
+        session_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Some(token))
+}
+
+// Record last usage of token
+async fn touch_auth_token(
+    session_id: i32,
+    last_used_at: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<(), AppError> {
+    query!(
+        "UPDATE auth_tokens
+        SET last_used_at = $1
+        WHERE id = $2;",
+        last_used_at,
+        session_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Add a new token to the user, only its digest is stored. User is expected to exist.
 pub async fn store_auth_token(
     name: &str,
     token: &str,
     created_at: DateTime<Utc>,
+    user_agent: Option<&str>,
+    device_name: Option<&str>,
     db: &PgPool,
 ) -> Result<(), AppError> {
     query!(
-        "INSERT INTO auth_tokens (token, created_at, user_id)
-        VALUES ($1, $2, (SELECT id FROM users WHERE username=$3));",
-        token,
+        "INSERT INTO auth_tokens
+            (token, created_at, session_started_at, last_used_at, user_agent, device_name, user_id)
+        VALUES ($1, $2, $2, $2, $3, $4, (SELECT id FROM users WHERE username=$5));",
+        hash_token(token),
         created_at,
+        user_agent,
+        device_name,
         name
     )
     .execute(db)
@@ -134,13 +371,32 @@ pub async fn store_auth_token(
     Ok(())
 }
 
-// Delete provided auth token from db
-pub async fn delete_auth_token(token: &str, db: &PgPool) -> Result<(), AppError> {
+// Delete a single auth token of user from db, returns whether it existed
+pub async fn delete_auth_token_by_id(
+    session_id: i32,
+    user_id: i32,
+    db: &PgPool,
+) -> Result<bool, AppError> {
+    let result = query!(
+        "DELETE
+        FROM auth_tokens
+        WHERE id = $1 AND user_id = $2",
+        session_id,
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+// Delete all API tokens of user from db
+pub async fn delete_all_api_tokens(user_id: i32, db: &PgPool) -> Result<(), AppError> {
     query!(
         "DELETE
-        FROM auth_tokens 
-        WHERE token = $1",
-        &token
+        FROM api_tokens
+        WHERE user_id = $1",
+        user_id
     )
     .execute(db)
     .await?;
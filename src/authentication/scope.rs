@@ -0,0 +1,33 @@
+use serde::{Deserialize, Serialize};
+use utoipa::ToSchema;
+
+/// Permission granted to an API token. Routes accessible with API tokens declare
+/// the scope they require as an extension, all other routes require a session.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize, ToSchema)]
+pub enum Scope {
+    #[serde(rename = "notes:read")]
+    NotesRead,
+    #[serde(rename = "notes:write")]
+    NotesWrite,
+    #[serde(rename = "shares:manage")]
+    SharesManage,
+}
+
+impl Scope {
+    pub fn as_str(&self) -> &'static str {
+        match self {
+            Scope::NotesRead => "notes:read",
+            Scope::NotesWrite => "notes:write",
+            Scope::SharesManage => "shares:manage",
+        }
+    }
+
+    pub fn from_name(name: &str) -> Option<Scope> {
+        match name {
+            "notes:read" => Some(Scope::NotesRead),
+            "notes:write" => Some(Scope::NotesWrite),
+            "shares:manage" => Some(Scope::SharesManage),
+            _ => None,
+        }
+    }
+}
@@ -0,0 +1,61 @@
+use axum::{
+    http::{header::SET_COOKIE, Request},
+    middleware::Next,
+    response::Response,
+};
+use chrono::Duration;
+use std::sync::{Arc, Mutex};
+
+use crate::{config::SharedConfig, util::get_header_with_token};
+
+/// Expiration rules of session tokens
+#[derive(Clone)]
+pub struct SessionPolicy {
+    /// Sessions not used for this long expire
+    pub idle_timeout: Duration,
+    /// Sessions expire this long after login, regardless of usage and token rotation
+    pub lifetime: Duration,
+    /// Tokens issued earlier than this before the end of their session are replaced by a new one
+    /// when used
+    pub renewal_window: Duration,
+}
+
+/// Slot for a rotated session token that has to be sent to the client, along with the
+/// remaining lifetime of its session
+#[derive(Clone, Default)]
+pub struct SessionRenewal(Arc<Mutex<Option<(String, Duration)>>>);
+
+impl SessionRenewal {
+    pub fn set(&self, token: String, remaining: Duration) {
+        *self.0.lock().expect("session renewal lock poisoned") = Some((token, remaining));
+    }
+
+    fn take(&self) -> Option<(String, Duration)> {
+        self.0.lock().expect("session renewal lock poisoned").take()
+    }
+}
+
+/// Middleware setting the cookie of session tokens rotated while handling the request
+pub async fn session_renewal<B>(mut req: Request<B>, next: Next<B>) -> Response {
+    let renewal = SessionRenewal::default();
+    let config = req
+        .extensions()
+        .get::<SharedConfig>()
+        .expect("config missing")
+        .clone();
+
+    req.extensions_mut().insert(renewal.clone());
+
+    let mut response = next.run(req).await;
+
+    // Handlers setting the cookie themselves (e.g. logout) take precedence
+    if let Some((token, remaining)) = renewal.take() {
+        if !response.headers().contains_key(SET_COOKIE) {
+            response
+                .headers_mut()
+                .extend(get_header_with_token(&token, remaining, &config.cookie));
+        }
+    }
+
+    response
+}
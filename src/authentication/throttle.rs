@@ -0,0 +1,211 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{query, PgPool};
+use std::net::IpAddr;
+use tracing::{error, warn};
+
+use crate::{
+    error::AppError,
+    mailer::{Mail, SharedMailer},
+};
+
+/// Failed attempts allowed before backoff starts
+const FREE_ATTEMPTS: i32 = 3;
+
+/// Upper bound of the backoff between attempts: 15 minutes
+const MAX_BACKOFF_SECONDS: i64 = 15 * 60;
+
+/// Failed attempts on a single account until it is locked
+const ACCOUNT_LOCKOUT_THRESHOLD: i32 = 10;
+
+/// Failed attempts from a single address until it is locked
+const IP_LOCKOUT_THRESHOLD: i32 = 50;
+
+/// Duration of a lockout: 1 hour
+const LOCKOUT_MINUTES: i64 = 60;
+
+/// Failures older than this are forgotten: 1 day
+pub const FAILURE_RETENTION_HOURS: i64 = 24;
+
+/// Tracks failed password checks per account and per client address
+#[derive(Clone)]
+pub struct LoginThrottle {
+    mailer: SharedMailer,
+}
+
+impl LoginThrottle {
+    pub fn new(mailer: SharedMailer) -> Self {
+        LoginThrottle { mailer }
+    }
+
+    /// Reject the attempt if account or address are currently backing off or locked
+    pub async fn check(&self, username: &str, ip: IpAddr, db: &PgPool) -> Result<(), AppError> {
+        let now = Utc::now();
+
+        let row = query!(
+            "SELECT MAX(locked_until) AS locked_until
+            FROM login_failures
+            WHERE key = $1 OR key = $2;",
+            account_key(username),
+            ip_key(ip),
+        )
+        .fetch_one(db)
+        .await?;
+
+        match row.locked_until {
+            Some(locked_until) if locked_until > now => Err(AppError::RateLimited {
+                retry_after: (locked_until - now).num_seconds() + 1,
+            }),
+            _ => Ok(()),
+        }
+    }
+
+    /// Record a failed attempt, locking account or address once their threshold is reached
+    pub async fn record_failure(
+        &self,
+        username: &str,
+        ip: IpAddr,
+        db: &PgPool,
+    ) -> Result<(), AppError> {
+        let now = Utc::now();
+
+        let account_failures = record_failure(&account_key(username), now, db).await?;
+        let ip_failures = record_failure(&ip_key(ip), now, db).await?;
+
+        lock(
+            &account_key(username),
+            account_failures,
+            ACCOUNT_LOCKOUT_THRESHOLD,
+            now,
+            db,
+        )
+        .await?;
+        lock(&ip_key(ip), ip_failures, IP_LOCKOUT_THRESHOLD, now, db).await?;
+
+        if account_failures >= ACCOUNT_LOCKOUT_THRESHOLD {
+            warn!("Account {} locked after failed login attempts", username);
+            self.notify_account_locked(username, db).await;
+        }
+
+        if ip_failures >= IP_LOCKOUT_THRESHOLD {
+            warn!("Address {} locked after failed login attempts", ip);
+        }
+
+        Ok(())
+    }
+
+    /// Forget failed attempts on the account after a successful password check or reset
+    pub async fn record_success(&self, username: &str, db: &PgPool) -> Result<(), AppError> {
+        query!(
+            "DELETE
+            FROM login_failures
+            WHERE key = $1;",
+            account_key(username),
+        )
+        .execute(db)
+        .await?;
+
+        Ok(())
+    }
+
+    // Let the account owner know about the lockout, if they have an email address
+    async fn notify_account_locked(&self, username: &str, db: &PgPool) {
+        let email = match query!(
+            "SELECT email
+            FROM users
+            WHERE lower(username) = lower($1);",
+            username
+        )
+        .fetch_optional(db)
+        .await
+        {
+            Ok(Some(row)) => row.email,
+            Ok(None) => None,
+            Err(err) => {
+                error!("Loading email for lockout notification failed: {:?}", err);
+                None
+            }
+        };
+
+        if let Some(email) = email {
+            let mail = Mail {
+                to: email,
+                subject: "Fieldnotes account locked".to_string(),
+                body: format!(
+                    "Hi {},\n\n\
+                    there were {} failed attempts to log in to your account, so it has been \
+                    locked for {} minutes. If this wasn't you, consider changing your password.",
+                    username, ACCOUNT_LOCKOUT_THRESHOLD, LOCKOUT_MINUTES
+                ),
+            };
+
+            if let Err(err) = self.mailer.send(mail).await {
+                error!("Sending lockout notification failed: {:?}", err);
+            }
+        }
+    }
+}
+
+fn account_key(username: &str) -> String {
+    format!("account:{}", username.to_lowercase())
+}
+
+fn ip_key(ip: IpAddr) -> String {
+    format!("ip:{}", ip)
+}
+
+// Exponential backoff after the free attempts, lockout once the threshold is reached
+fn locked_until(failures: i32, threshold: i32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    if failures >= threshold {
+        Some(now + Duration::minutes(LOCKOUT_MINUTES))
+    } else if failures > FREE_ATTEMPTS {
+        let exponent = (failures - FREE_ATTEMPTS - 1).min(20) as u32;
+        let backoff = 2i64.pow(exponent).min(MAX_BACKOFF_SECONDS);
+        Some(now + Duration::seconds(backoff))
+    } else {
+        None
+    }
+}
+
+// Count failure, restarting the count if the previous failure is too long ago
+async fn record_failure(key: &str, now: DateTime<Utc>, db: &PgPool) -> Result<i32, AppError> {
+    let row = query!(
+        "INSERT INTO login_failures (key, failures, last_failure_at)
+        VALUES ($1, 1, $2)
+        ON CONFLICT (key) DO UPDATE
+        SET failures = CASE
+                WHEN login_failures.last_failure_at < $3 THEN 1
+                ELSE login_failures.failures + 1
+            END,
+            last_failure_at = EXCLUDED.last_failure_at
+        RETURNING failures;",
+        key,
+        now,
+        now - Duration::hours(FAILURE_RETENTION_HOURS),
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.failures)
+}
+
+// A lockout starts the count over, so later failures don't extend it indefinitely
+async fn lock(
+    key: &str,
+    failures: i32,
+    threshold: i32,
+    now: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<(), AppError> {
+    query!(
+        "UPDATE login_failures
+        SET locked_until = $1, failures = CASE WHEN $2 THEN 0 ELSE failures END
+        WHERE key = $3;",
+        locked_until(failures, threshold, now),
+        failures >= threshold,
+        key,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,134 @@
+mod notes;
+mod sessions;
+mod stats;
+mod user;
+
+use clap::{Parser, Subcommand};
+use sqlx::PgPool;
+use thiserror::Error;
+
+use crate::{
+    config::Config,
+    error::AppError,
+    migrations::{run_migrations, MigrationError},
+};
+
+/// Command line interface of the server binary
+#[derive(Parser)]
+#[clap(version, about = "Fieldnotes API server and administration")]
+pub struct Cli {
+    /// Defaults to serving the API
+    #[clap(subcommand)]
+    pub command: Option<Command>,
+}
+
+#[derive(Subcommand)]
+pub enum Command {
+    /// Serve the API
+    Serve,
+    /// Apply pending database migrations and exit
+    Migrate,
+    /// Manage user accounts
+    #[clap(subcommand)]
+    User(UserCommand),
+    /// Manage login sessions
+    #[clap(subcommand)]
+    Sessions(SessionsCommand),
+    /// Manage notes
+    #[clap(subcommand)]
+    Notes(NotesCommand),
+    /// Print usage statistics of the instance
+    Stats,
+}
+
+#[derive(Subcommand)]
+pub enum UserCommand {
+    /// List all users with their storage usage
+    List,
+    /// Create a user, the password is read from stdin
+    Create {
+        name: String,
+        #[clap(long)]
+        email: Option<String>,
+        /// Grant the admin role
+        #[clap(long)]
+        admin: bool,
+    },
+    /// Disable a user, logging out all sessions and revoking API tokens
+    Disable { name: String },
+    /// Delete a user with all associated data
+    Delete { name: String },
+}
+
+#[derive(Subcommand)]
+pub enum SessionsCommand {
+    /// Delete expired sessions, or all sessions of a single user
+    Purge {
+        #[clap(long)]
+        user: Option<String>,
+    },
+}
+
+#[derive(Subcommand)]
+pub enum NotesCommand {
+    /// Delete notes that have been in the trash longer than the retention period
+    PurgeTrash,
+}
+
+#[derive(Error, Debug)]
+pub enum CliError {
+    #[error("User {0} not found")]
+    UserNotFound(String),
+
+    #[error("User {0} already exists")]
+    UserExists(String),
+
+    #[error("Username {0} is invalid")]
+    InvalidUsername(String),
+
+    #[error("Password missing")]
+    PasswordMissing,
+
+    #[error("Reading password failed: {0}")]
+    Io(#[from] std::io::Error),
+
+    #[error("Hashing password failed: {0}")]
+    Hash(#[from] bcrypt::BcryptError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error(transparent)]
+    Migration(#[from] MigrationError),
+
+    #[error(transparent)]
+    App(AppError),
+}
+
+impl From<AppError> for CliError {
+    fn from(error: AppError) -> Self {
+        match error {
+            AppError::DBError(error) => CliError::Database(error),
+            error => CliError::App(error),
+        }
+    }
+}
+
+/// Run an administrative command to completion
+pub async fn run(command: Command, config: &Config, db: &PgPool) -> Result<(), CliError> {
+    match command {
+        Command::Serve => unreachable!("Serving is handled by main"),
+        Command::Migrate => Ok(run_migrations(db).await?),
+        Command::User(UserCommand::List) => user::list(db).await,
+        Command::User(UserCommand::Create { name, email, admin }) => {
+            user::create(&name, email, admin, config, db).await
+        }
+        Command::User(UserCommand::Disable { name }) => user::disable(&name, db).await,
+        Command::User(UserCommand::Delete { name }) => user::delete(&name, db).await,
+        Command::Sessions(SessionsCommand::Purge { user }) => {
+            sessions::purge(user.as_deref(), config, db).await
+        }
+        Command::Notes(NotesCommand::PurgeTrash) => notes::purge_trash(config, db).await,
+        Command::Stats => stats::print(db).await,
+    }
+}
@@ -0,0 +1,17 @@
+use sqlx::PgPool;
+
+use crate::{config::Config, schedule::delete_expired_notes};
+
+use super::CliError;
+
+pub async fn purge_trash(config: &Config, db: &PgPool) -> Result<(), CliError> {
+    let count = delete_expired_notes(db, config.trash_retention).await?;
+
+    println!(
+        "Deleted {} notes in the trash for longer than {} days",
+        count,
+        config.trash_retention.num_days()
+    );
+
+    Ok(())
+}
@@ -0,0 +1,25 @@
+use sqlx::PgPool;
+
+use crate::{
+    authentication::delete_all_auth_tokens, config::Config, schedule::delete_expired_tokens,
+};
+
+use super::{user::find_user, CliError};
+
+pub async fn purge(user: Option<&str>, config: &Config, db: &PgPool) -> Result<(), CliError> {
+    match user {
+        Some(name) => {
+            let user_id = find_user(name, db).await?;
+            delete_all_auth_tokens(user_id, db).await?;
+
+            println!("Deleted all sessions of user {}", name);
+        }
+        None => {
+            let count = delete_expired_tokens(db, &config.session).await?;
+
+            println!("Deleted {} expired sessions", count);
+        }
+    }
+
+    Ok(())
+}
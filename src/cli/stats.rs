@@ -0,0 +1,33 @@
+use sqlx::{query, PgPool};
+
+use super::CliError;
+
+pub async fn print(db: &PgPool) -> Result<(), CliError> {
+    let stats = query!(
+        r#"SELECT
+            (SELECT COUNT(*) FROM users WHERE deleted_at IS NULL AND disabled_at IS NULL) AS "active_users!",
+            (SELECT COUNT(*) FROM users WHERE deleted_at IS NOT NULL AND disabled_at IS NULL) AS "deactivated_users!",
+            (SELECT COUNT(*) FROM users WHERE disabled_at IS NOT NULL) AS "disabled_users!",
+            (SELECT COUNT(*) FROM notes WHERE deleted_at IS NULL) AS "notes!",
+            (SELECT COUNT(*) FROM notes WHERE deleted_at IS NOT NULL) AS "trashed_notes!",
+            (SELECT COUNT(*) FROM shares) AS "shares!",
+            (SELECT COUNT(*) FROM auth_tokens) AS "sessions!",
+            (SELECT COUNT(*) FROM api_tokens) AS "api_tokens!",
+            (SELECT COALESCE(SUM(octet_length(metadata) + octet_length(key) + octet_length(content)), 0)
+                FROM notes)::bigint AS "storage_bytes!";"#
+    )
+    .fetch_one(db)
+    .await?;
+
+    println!("Active users:      {}", stats.active_users);
+    println!("Deactivated users: {}", stats.deactivated_users);
+    println!("Disabled users:    {}", stats.disabled_users);
+    println!("Notes:             {}", stats.notes);
+    println!("Notes in trash:    {}", stats.trashed_notes);
+    println!("Shares:            {}", stats.shares);
+    println!("Sessions:          {}", stats.sessions);
+    println!("API tokens:        {}", stats.api_tokens);
+    println!("Storage bytes:     {}", stats.storage_bytes);
+
+    Ok(())
+}
@@ -0,0 +1,113 @@
+use bcrypt::hash;
+use chrono::Utc;
+use sqlx::PgPool;
+use std::io::{stdin, BufRead};
+
+use crate::{
+    admin::{list_users, set_disabled_at},
+    authentication::{delete_all_api_tokens, delete_all_auth_tokens, ADMIN_ROLE, USER_ROLE},
+    config::Config,
+    error::AppError,
+    users::{delete_all_user_data, get_user_id, store_user, user_exists, username_valid},
+};
+
+use super::CliError;
+
+pub async fn list(db: &PgPool) -> Result<(), CliError> {
+    println!(
+        "{:>6}  {:<24} {:<6} {:<9} {:>6} {:>6} {:>12}  LAST ACTIVE",
+        "ID", "USERNAME", "ROLE", "STATUS", "NOTES", "SHARES", "BYTES"
+    );
+
+    for user in list_users(db).await? {
+        let status = if user.disabled_at.is_some() {
+            "disabled"
+        } else if user.deleted_at.is_some() {
+            "deleted"
+        } else {
+            "active"
+        };
+
+        println!(
+            "{:>6}  {:<24} {:<6} {:<9} {:>6} {:>6} {:>12}  {}",
+            user.id,
+            user.username,
+            user.role,
+            status,
+            user.notes,
+            user.shares,
+            user.storage_bytes,
+            user.last_active_at
+                .map(|time| time.format("%F %T").to_string())
+                .unwrap_or_else(|| "-".to_string()),
+        );
+    }
+
+    Ok(())
+}
+
+pub async fn create(
+    name: &str,
+    email: Option<String>,
+    admin: bool,
+    config: &Config,
+    db: &PgPool,
+) -> Result<(), CliError> {
+    if !username_valid(name) {
+        return Err(CliError::InvalidUsername(name.to_string()));
+    }
+
+    if user_exists(name, db).await? {
+        return Err(CliError::UserExists(name.to_string()));
+    }
+
+    let mut password = String::new();
+    stdin().lock().read_line(&mut password)?;
+    let password = password.trim_end_matches(&['\r', '\n'][..]);
+
+    if password.is_empty() {
+        return Err(CliError::PasswordMissing);
+    }
+
+    let hashed_password = hash(password, config.bcrypt_cost)?;
+
+    let role = if admin { ADMIN_ROLE } else { USER_ROLE };
+
+    store_user(name, &hashed_password, email, role, None, Utc::now(), db).await?;
+
+    println!("Created user {}", name);
+
+    Ok(())
+}
+
+pub async fn disable(name: &str, db: &PgPool) -> Result<(), CliError> {
+    let user_id = find_user(name, db).await?;
+
+    set_disabled_at(user_id, Some(Utc::now()), db).await?;
+    delete_all_auth_tokens(user_id, db).await?;
+    delete_all_api_tokens(user_id, db).await?;
+
+    println!("Disabled user {}", name);
+
+    Ok(())
+}
+
+pub async fn delete(name: &str, db: &PgPool) -> Result<(), CliError> {
+    let user_id = find_user(name, db).await?;
+
+    delete_all_user_data(user_id, db).await?;
+
+    println!("Deleted user {} with all data", name);
+
+    Ok(())
+}
+
+pub async fn find_user(name: &str, db: &PgPool) -> Result<i32, CliError> {
+    match get_user_id(name, db).await {
+        Ok(user_id) => Ok(user_id),
+        Err(AppError::DBError(sqlx::Error::RowNotFound)) => {
+            Err(CliError::UserNotFound(name.to_string()))
+        }
+        Err(error) => Err(error.into()),
+    }
+}
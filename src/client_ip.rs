@@ -0,0 +1,49 @@
+use axum::{
+    async_trait,
+    extract::{ConnectInfo, FromRequest, RequestParts},
+};
+use std::net::{IpAddr, SocketAddr};
+
+use crate::{config::SharedConfig, error::AppError};
+
+/// Header appended to by the reverse proxy in front of the server
+const FORWARDED_FOR_HEADER: &str = "x-forwarded-for";
+
+/// Address of the client sending the request
+pub struct ClientIp(pub IpAddr);
+
+#[async_trait]
+impl<B> FromRequest<B> for ClientIp
+where
+    B: Send,
+{
+    type Rejection = AppError;
+
+    async fn from_request(req: &mut RequestParts<B>) -> Result<Self, Self::Rejection> {
+        let trust_proxy_headers = req
+            .extensions()
+            .get::<SharedConfig>()
+            .map(|config| config.trust_proxy_headers)
+            .unwrap_or(false);
+
+        if trust_proxy_headers {
+            // The last entry is the one added by our own proxy, earlier ones are client supplied
+            if let Some(ip) = req
+                .headers()
+                .get(FORWARDED_FOR_HEADER)
+                .and_then(|value| value.to_str().ok())
+                .and_then(|value| value.rsplit(',').next())
+                .and_then(|ip| ip.trim().parse().ok())
+            {
+                return Ok(ClientIp(ip));
+            }
+        }
+
+        match req.extensions().get::<ConnectInfo<SocketAddr>>() {
+            Some(ConnectInfo(addr)) => Ok(ClientIp(addr.ip())),
+            None => Err(AppError::ViolatedAssertion(
+                "Connection info missing".to_string(),
+            )),
+        }
+    }
+}
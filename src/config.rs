@@ -0,0 +1,433 @@
+use chrono::Duration;
+use hyper::header::HeaderValue;
+use std::{fmt::Display, net::SocketAddr, str::FromStr, sync::Arc};
+use tracing::level_filters::LevelFilter;
+
+use crate::{
+    authentication::SessionPolicy,
+    mailer::{MailConfig, MailTransport},
+    rate_limit::{Limit, RateLimits},
+    users::{AccountDeletionPolicy, PasswordResetConfig, SignupMode},
+    util::CookieConfig,
+};
+
+/// Env variable pointing to an optional TOML configuration file
+const CONFIG_FILE_ENV: &str = "CONFIG_FILE";
+
+/// Longest duration that can be configured, a hundred years
+const MAX_DURATION_DAYS: i64 = 36_500;
+
+/// Output format of log lines
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human readable lines
+    Text,
+    /// One JSON object per line, for log shippers
+    Json,
+}
+
+impl FromStr for LogFormat {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "text" => Ok(LogFormat::Text),
+            "json" => Ok(LogFormat::Json),
+            _ => Err(format!("unknown log format {}", name)),
+        }
+    }
+}
+
+/// Configuration shared with handlers and middleware
+pub type SharedConfig = Arc<Config>;
+
+/// Server configuration, loaded from env variables and an optional TOML file.
+/// Env variables take precedence over the file.
+pub struct Config {
+    pub database_url: String,
+    pub db_max_connections: u32,
+    /// Apply pending migrations at startup
+    pub run_migrations: bool,
+    pub listen: SocketAddr,
+    /// Address serving Prometheus metrics, disabled if not set
+    pub metrics_listen: Option<SocketAddr>,
+    pub log_level: LevelFilter,
+    pub log_format: LogFormat,
+    /// Origins of web apps allowed to make requests with credentials
+    pub origins: Vec<String>,
+    pub bcrypt_cost: u32,
+    pub trust_proxy_headers: bool,
+    pub session: SessionPolicy,
+    pub cookie: CookieConfig,
+    pub password_reset: PasswordResetConfig,
+    pub signup_mode: SignupMode,
+    pub account_deletion: AccountDeletionPolicy,
+    pub rate_limits: RateLimits,
+    pub mail: MailConfig,
+    /// Deleted notes are purged after this long
+    pub trash_retention: Duration,
+    pub schedules: ScheduleConfig,
+    /// Time in-flight requests and running jobs get to finish on shutdown
+    pub shutdown_timeout: Duration,
+}
+
+/// Intervals of the background jobs
+#[derive(Clone)]
+pub struct ScheduleConfig {
+    pub notes_deletion: Duration,
+    pub tokens_deletion: Duration,
+    pub users_deletion: Duration,
+}
+
+impl Config {
+    /// Load and validate the configuration, reporting all problems at once
+    pub fn load() -> Result<Config, Vec<String>> {
+        let mut source = Source::new();
+
+        let database_url = source.required("DATABASE_URL", "database.url");
+        let db_max_connections = source.or("DB_MAX_CONNECTIONS", "database.max_connections", 100);
+        let run_migrations = source.or("RUN_MIGRATIONS", "database.run_migrations", true);
+        let listen = source.required("LISTEN", "listen");
+        let metrics_listen = source.optional("METRICS_LISTEN", "metrics.listen");
+        let log_level = source.or("LOG_LEVEL", "log_level", LevelFilter::WARN);
+        let log_format = source.or("LOG_FORMAT", "log_format", LogFormat::Text);
+        let bcrypt_cost = source.or("BCRYPT_COST", "bcrypt_cost", 12);
+        let trust_proxy_headers = source.or("TRUST_PROXY_HEADERS", "trust_proxy_headers", false);
+
+        let write_app: Option<String> = source.required("WRITE_APP", "write_app");
+        let origins = source.origins(write_app.as_deref());
+
+        let session = SessionPolicy {
+            idle_timeout: source.duration(
+                "SESSION_IDLE_TIMEOUT_DAYS",
+                "session.idle_timeout_days",
+                14,
+                Duration::days,
+            ),
+            lifetime: source.duration(
+                "SESSION_LIFETIME_DAYS",
+                "session.lifetime_days",
+                56,
+                Duration::days,
+            ),
+            renewal_window: source.duration(
+                "SESSION_RENEWAL_DAYS",
+                "session.renewal_days",
+                14,
+                Duration::days,
+            ),
+        };
+
+        let cookie = CookieConfig {
+            name: source.or("COOKIE_NAME", "cookie.name", "token".to_string()),
+            domain: source.optional("COOKIE_DOMAIN", "cookie.domain"),
+            path: source.or("COOKIE_PATH", "cookie.path", "/".to_string()),
+        };
+
+        let password_reset_url = source.optional("PASSWORD_RESET_URL", "password_reset_url");
+        let signup_mode = source.or("SIGNUP_MODE", "signup_mode", SignupMode::Open);
+
+        let account_deletion = AccountDeletionPolicy {
+            grace_period: source.duration(
+                "ACCOUNT_DELETION_GRACE_DAYS",
+                "account_deletion_grace_days",
+                30,
+                Duration::days,
+            ),
+        };
+
+        let rate_limits = RateLimits {
+            auth: source.limit("AUTH", "auth", 10, 10),
+            shares: source.limit("SHARES", "shares", 60, 120),
+            api: source.limit("API", "api", 300, 600),
+        };
+
+        let mail = MailConfig {
+            transport: source.or("MAILER", "mail.transport", MailTransport::Log),
+            sendmail_path: source.or(
+                "SENDMAIL_PATH",
+                "mail.sendmail_path",
+                "/usr/sbin/sendmail".to_string(),
+            ),
+            from: source.optional("MAIL_FROM", "mail.from"),
+        };
+
+        let trash_retention = source.duration(
+            "TRASH_RETENTION_DAYS",
+            "trash_retention_days",
+            28,
+            Duration::days,
+        );
+
+        let schedules = ScheduleConfig {
+            notes_deletion: source.duration(
+                "NOTES_DELETION_INTERVAL_HOURS",
+                "schedule.notes_deletion_hours",
+                7,
+                Duration::hours,
+            ),
+            tokens_deletion: source.duration(
+                "TOKENS_DELETION_INTERVAL_HOURS",
+                "schedule.tokens_deletion_hours",
+                3,
+                Duration::hours,
+            ),
+            users_deletion: source.duration(
+                "USERS_DELETION_INTERVAL_HOURS",
+                "schedule.users_deletion_hours",
+                11,
+                Duration::hours,
+            ),
+        };
+
+        let shutdown_timeout = source.duration(
+            "SHUTDOWN_TIMEOUT_SECONDS",
+            "shutdown_timeout_seconds",
+            30,
+            Duration::seconds,
+        );
+
+        if db_max_connections == 0 {
+            source.error("DB_MAX_CONNECTIONS must be positive");
+        }
+        if !(4..=31).contains(&bcrypt_cost) {
+            source.error("BCRYPT_COST must be between 4 and 31");
+        }
+        if session.lifetime <= Duration::zero() {
+            source.error("SESSION_LIFETIME_DAYS must be positive");
+        }
+        if session.renewal_window < Duration::zero() {
+            source.error("SESSION_RENEWAL_DAYS must not be negative");
+        }
+        if session.renewal_window >= session.lifetime {
+            source.error("SESSION_RENEWAL_DAYS must be smaller than SESSION_LIFETIME_DAYS");
+        }
+        if session.idle_timeout <= Duration::zero() {
+            source.error("SESSION_IDLE_TIMEOUT_DAYS must be positive");
+        }
+        if account_deletion.grace_period < Duration::zero() {
+            source.error("ACCOUNT_DELETION_GRACE_DAYS must not be negative");
+        }
+        if [
+            schedules.notes_deletion,
+            schedules.tokens_deletion,
+            schedules.users_deletion,
+        ]
+        .iter()
+        .any(|interval| *interval <= Duration::zero())
+        {
+            source.error("Schedule intervals must be positive");
+        }
+        if trash_retention <= Duration::zero() {
+            source.error("TRASH_RETENTION_DAYS must be positive");
+        }
+        if shutdown_timeout < Duration::zero() {
+            source.error("SHUTDOWN_TIMEOUT_SECONDS must not be negative");
+        }
+        if mail.transport == MailTransport::Sendmail && mail.from.is_none() {
+            source.error("MAIL_FROM (mail.from) is required for the sendmail transport");
+        }
+
+        match (database_url, listen, write_app) {
+            (Some(database_url), Some(listen), Some(write_app)) if source.errors.is_empty() => {
+                Ok(Config {
+                    database_url,
+                    db_max_connections,
+                    run_migrations,
+                    listen,
+                    metrics_listen,
+                    log_level,
+                    log_format,
+                    origins,
+                    bcrypt_cost,
+                    trust_proxy_headers,
+                    session,
+                    cookie,
+                    password_reset: PasswordResetConfig {
+                        link_base: password_reset_url
+                            .unwrap_or_else(|| format!("{}/reset", write_app)),
+                    },
+                    signup_mode,
+                    account_deletion,
+                    rate_limits,
+                    mail,
+                    trash_retention,
+                    schedules,
+                    shutdown_timeout,
+                })
+            }
+            _ => Err(source.errors),
+        }
+    }
+}
+
+// Values from env variables and the configuration file, collecting errors along the way
+struct Source {
+    file: Option<toml::Value>,
+    errors: Vec<String>,
+}
+
+impl Source {
+    fn new() -> Self {
+        let mut errors = Vec::new();
+
+        let file = match dotenv::var(CONFIG_FILE_ENV) {
+            Ok(path) => match std::fs::read_to_string(&path) {
+                Ok(content) => match content.parse::<toml::Value>() {
+                    Ok(value) => Some(value),
+                    Err(err) => {
+                        errors.push(format!("Config file {} malformed: {}", path, err));
+                        None
+                    }
+                },
+                Err(err) => {
+                    errors.push(format!("Config file {} unreadable: {}", path, err));
+                    None
+                }
+            },
+            Err(_) => None,
+        };
+
+        Source { file, errors }
+    }
+
+    fn error(&mut self, message: &str) {
+        self.errors.push(message.to_string());
+    }
+
+    // Raw value from env or file, file values are converted to their string representation
+    fn raw(&self, env: &str, path: &str) -> Option<String> {
+        if let Ok(value) = dotenv::var(env) {
+            return Some(value);
+        }
+
+        let mut value = self.file.as_ref()?;
+        for key in path.split('.') {
+            value = value.get(key)?;
+        }
+
+        Some(match value {
+            toml::Value::String(value) => value.clone(),
+            toml::Value::Array(values) => values
+                .iter()
+                .map(|value| match value {
+                    toml::Value::String(value) => value.clone(),
+                    value => value.to_string(),
+                })
+                .collect::<Vec<_>>()
+                .join(","),
+            value => value.to_string(),
+        })
+    }
+
+    fn optional<T>(&mut self, env: &str, path: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        let value = self.raw(env, path)?;
+
+        match value.parse() {
+            Ok(value) => Some(value),
+            Err(err) => {
+                self.errors
+                    .push(format!("{} ({}) malformed: {}", env, path, err));
+                None
+            }
+        }
+    }
+
+    fn or<T>(&mut self, env: &str, path: &str, default: T) -> T
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        self.optional(env, path).unwrap_or(default)
+    }
+
+    fn required<T>(&mut self, env: &str, path: &str) -> Option<T>
+    where
+        T: FromStr,
+        T::Err: Display,
+    {
+        if self.raw(env, path).is_none() {
+            self.errors.push(format!("{} ({}) missing", env, path));
+        }
+
+        self.optional(env, path)
+    }
+
+    // Durations are checked before conversion, which panics for values out of range
+    fn duration(
+        &mut self,
+        env: &str,
+        path: &str,
+        default: i64,
+        unit: fn(i64) -> Duration,
+    ) -> Duration {
+        let value = self.or(env, path, default);
+        let max = Duration::days(MAX_DURATION_DAYS).num_seconds() / unit(1).num_seconds();
+
+        if !(-max..=max).contains(&value) {
+            self.errors.push(format!(
+                "{} ({}) out of range, the limit is {}",
+                env, path, max
+            ));
+            return unit(default);
+        }
+
+        unit(value)
+    }
+
+    fn limit(&mut self, env_group: &str, path_group: &str, burst: u32, per_minute: u32) -> Limit {
+        let limit = Limit {
+            burst: self.or(
+                &format!("RATE_LIMIT_{}_BURST", env_group),
+                &format!("rate_limit.{}.burst", path_group),
+                burst,
+            ),
+            per_minute: self.or(
+                &format!("RATE_LIMIT_{}_PER_MINUTE", env_group),
+                &format!("rate_limit.{}.per_minute", path_group),
+                per_minute,
+            ),
+        };
+
+        if limit.burst == 0 || limit.per_minute == 0 {
+            self.errors
+                .push(format!("RATE_LIMIT_{} limits must be positive", env_group));
+        }
+
+        limit
+    }
+
+    // Allowed origins: the write app, the read apps of the original deployment and any
+    // number of additional comma-separated origins
+    fn origins(&mut self, write_app: Option<&str>) -> Vec<String> {
+        let mut origins: Vec<String> = write_app.map(str::to_string).into_iter().collect();
+
+        for (env, path) in [
+            ("READ_APP", "read_app"),
+            ("READ_APP_WWW", "read_app_www"),
+            ("ALLOWED_ORIGINS", "origins"),
+        ] {
+            if let Some(value) = self.raw(env, path) {
+                origins.extend(
+                    value
+                        .split(',')
+                        .map(str::trim)
+                        .filter(|origin| !origin.is_empty())
+                        .map(str::to_string),
+                );
+            }
+        }
+
+        for origin in &origins {
+            if HeaderValue::from_str(origin).is_err() {
+                self.errors.push(format!("Origin {} malformed", origin));
+            }
+        }
+
+        origins
+    }
+}
@@ -0,0 +1,152 @@
+use axum::{
+    http::{header, HeaderMap, Method, Request, Uri},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use tracing::warn;
+
+use crate::{
+    config::SharedConfig,
+    error::AppError,
+    util::{get_bearer_token, get_cookie},
+};
+
+/// Header browser clients have to send with state-changing requests. Cross-origin
+/// requests can only set it after a successful CORS preflight.
+pub const CSRF_HEADER: &str = "x-requested-with";
+
+/// Error of requests failing the CSRF checks
+const CSRF_REJECTED: AppError = AppError::Forbidden {
+    code: "csrf_rejected",
+    message: "Request failed the CSRF checks",
+};
+
+/// Middleware rejecting state-changing requests that may originate from other sites
+pub async fn csrf_protection<B>(req: Request<B>, next: Next<B>) -> Response {
+    if is_safe_method(req.method()) {
+        return next.run(req).await;
+    }
+
+    let headers = req.headers();
+    let config = req
+        .extensions()
+        .get::<SharedConfig>()
+        .expect("config missing");
+
+    // Clients authenticating only through the Authorization header can't be forged by browsers
+    if get_bearer_token(headers).is_some() && get_cookie(headers, &config.cookie.name).is_none() {
+        return next.run(req).await;
+    }
+
+    if !headers.contains_key(CSRF_HEADER) {
+        warn!(
+            "Rejected {} {} without CSRF header",
+            req.method(),
+            req.uri()
+        );
+        return CSRF_REJECTED.into_response();
+    }
+
+    match request_origin(headers) {
+        Some(origin) if is_trusted(&config.origins, &origin) => next.run(req).await,
+        origin => {
+            warn!(
+                "Rejected {} {} from untrusted origin {:?}",
+                req.method(),
+                req.uri(),
+                origin
+            );
+            CSRF_REJECTED.into_response()
+        }
+    }
+}
+
+// Origins allowed to send state-changing requests with the session cookie
+fn is_trusted(origins: &[String], origin: &str) -> bool {
+    origins
+        .iter()
+        .any(|trusted| trusted.trim_end_matches('/') == origin)
+}
+
+fn is_safe_method(method: &Method) -> bool {
+    matches!(*method, Method::GET | Method::HEAD | Method::OPTIONS)
+}
+
+// Origin of the request from the Origin header, falling back to the Referer header
+fn request_origin(headers: &HeaderMap) -> Option<String> {
+    if let Some(origin) = headers.get(header::ORIGIN) {
+        return origin
+            .to_str()
+            .ok()
+            .map(|origin| origin.trim_end_matches('/').to_string());
+    }
+
+    let referer: Uri = headers.get(header::REFERER)?.to_str().ok()?.parse().ok()?;
+
+    Some(format!(
+        "{}://{}",
+        referer.scheme_str()?,
+        referer.authority()?
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use super::{is_trusted, request_origin};
+
+    fn headers(entries: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.insert(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn origin_header_preferred_over_referer() {
+        let headers = headers(&[
+            (header::ORIGIN, "https://app.example.com/"),
+            (header::REFERER, "https://other.example.com/notes"),
+        ]);
+
+        assert_eq!(
+            request_origin(&headers).as_deref(),
+            Some("https://app.example.com")
+        );
+    }
+
+    #[test]
+    fn origin_from_referer_keeps_port() {
+        let headers = headers(&[(header::REFERER, "http://localhost:3000/notes?id=1")]);
+
+        assert_eq!(
+            request_origin(&headers).as_deref(),
+            Some("http://localhost:3000")
+        );
+    }
+
+    #[test]
+    fn no_origin_without_headers_or_with_relative_referer() {
+        let relative = headers(&[(header::REFERER, "/notes")]);
+
+        assert_eq!(request_origin(&HeaderMap::new()), None);
+        assert_eq!(request_origin(&relative), None);
+    }
+
+    #[test]
+    fn trusted_origins_match_exactly() {
+        let origins = vec![
+            "https://app.example.com/".to_string(),
+            "http://localhost:3000".to_string(),
+        ];
+
+        assert!(is_trusted(&origins, "https://app.example.com"));
+        assert!(is_trusted(&origins, "http://localhost:3000"));
+        assert!(!is_trusted(&origins, "https://app.example.com.evil.com"));
+        assert!(!is_trusted(&origins, "http://app.example.com"));
+        assert!(!is_trusted(&origins, "http://localhost:3001"));
+        assert!(!is_trusted(&origins, "null"));
+    }
+}
@@ -1,38 +1,204 @@
-use axum::response::{IntoResponse, Response};
-use hyper::StatusCode;
-use log::error;
+use axum::{
+    extract::rejection::{JsonRejection, PathRejection, QueryRejection},
+    response::{IntoResponse, Response},
+    Json,
+};
+use hyper::{header::RETRY_AFTER, StatusCode};
+use serde::Serialize;
+use serde_json::{json, Value};
 use thiserror::Error;
+use tracing::error;
+use utoipa::ToSchema;
+
+use crate::{request_id::current_request_id, users::DeactivationResponse};
 
 #[derive(Error, Debug)]
 pub enum AppError {
     #[error("Database error")]
     DBError(#[from] sqlx::Error),
 
-    #[error("Conflict")]
-    Conflict,
+    /// The request conflicts with the current state, `code` tells which state
+    #[error("{message}")]
+    Conflict {
+        code: &'static str,
+        message: &'static str,
+    },
 
-    #[error("Unauthorized")]
+    #[error("Authentication required")]
     Unauthorized,
 
+    /// Username or password supplied with the request are wrong
+    #[error("Invalid username or password")]
+    InvalidCredentials,
+
+    /// The request is not allowed, `code` tells why
+    #[error("{message}")]
+    Forbidden {
+        code: &'static str,
+        message: &'static str,
+    },
+
+    /// Kind of the missing resource, e.g. "Note"
+    #[error("{0} not found")]
+    NotFound(&'static str),
+
+    /// Invalid value of a request field
+    #[error("{message}")]
+    Validation {
+        field: &'static str,
+        message: String,
+    },
+
+    #[error("Too many requests, retry after {retry_after} seconds")]
+    RateLimited { retry_after: i64 },
+
+    #[error("Request body larger than {limit} bytes")]
+    PayloadTooLarge { limit: u64 },
+
+    /// Login to an account pending deletion, which has to be reactivated explicitly
+    #[error("Account is deactivated and pending deletion")]
+    AccountDeactivated(DeactivationResponse),
+
+    /// Notes without a re-wrapped key when committing a key rotation
+    #[error("Notes are missing a re-wrapped key")]
+    KeysMissing(Vec<String>),
+
     #[error("{0}")]
     ViolatedAssertion(String),
 }
 
-impl IntoResponse for AppError {
-    fn into_response(self) -> Response {
-        let status = match self {
-            AppError::DBError(error) => {
-                error!("{:?}", error);
+impl AppError {
+    /// Stable machine-readable code of the error
+    pub fn code(&self) -> &'static str {
+        match self {
+            AppError::DBError(_) | AppError::ViolatedAssertion(_) => "internal_error",
+            AppError::Conflict { code, .. } | AppError::Forbidden { code, .. } => code,
+            AppError::Unauthorized => "unauthorized",
+            AppError::InvalidCredentials => "invalid_credentials",
+            AppError::NotFound(_) => "not_found",
+            AppError::Validation { .. } => "validation_failed",
+            AppError::RateLimited { .. } => "rate_limited",
+            AppError::PayloadTooLarge { .. } => "payload_too_large",
+            AppError::AccountDeactivated(_) => "account_deactivated",
+            AppError::KeysMissing(_) => "keys_missing",
+        }
+    }
+
+    pub fn status(&self) -> StatusCode {
+        match self {
+            AppError::DBError(_) | AppError::ViolatedAssertion(_) => {
                 StatusCode::INTERNAL_SERVER_ERROR
             }
-            AppError::Conflict => StatusCode::CONFLICT,
-            AppError::Unauthorized => StatusCode::UNAUTHORIZED,
-            AppError::ViolatedAssertion(assertion) => {
-                error!("{}", assertion);
-                StatusCode::INTERNAL_SERVER_ERROR
+            AppError::Conflict { .. } | AppError::AccountDeactivated(_) => StatusCode::CONFLICT,
+            AppError::Unauthorized | AppError::InvalidCredentials => StatusCode::UNAUTHORIZED,
+            AppError::Forbidden { .. } => StatusCode::FORBIDDEN,
+            AppError::NotFound(_) => StatusCode::NOT_FOUND,
+            AppError::Validation { .. } | AppError::KeysMissing(_) => {
+                StatusCode::UNPROCESSABLE_ENTITY
             }
+            AppError::RateLimited { .. } => StatusCode::TOO_MANY_REQUESTS,
+            AppError::PayloadTooLarge { .. } => StatusCode::PAYLOAD_TOO_LARGE,
+        }
+    }
+
+    // Additional machine-readable information about the error
+    fn details(&self) -> Option<Value> {
+        match self {
+            AppError::NotFound(resource) => Some(json!({ "resource": resource.to_lowercase() })),
+            AppError::Validation { field, .. } => Some(json!({ "field": field })),
+            AppError::RateLimited { retry_after } => Some(json!({ "retry_after": retry_after })),
+            AppError::PayloadTooLarge { limit } => Some(json!({ "limit_bytes": limit })),
+            AppError::AccountDeactivated(deactivation) => serde_json::to_value(deactivation).ok(),
+            AppError::KeysMissing(missing) => Some(json!({ "missing": missing })),
+            _ => None,
+        }
+    }
+}
+
+/// Body of error responses, the request id allows finding the matching log lines
+#[derive(Serialize, ToSchema)]
+pub struct ErrorResponse {
+    /// Stable machine-readable code, e.g. `not_found` or `username_taken`
+    code: &'static str,
+    message: String,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    #[schema(value_type = Option<Object>)]
+    details: Option<Value>,
+    request_id: Option<String>,
+}
+
+impl IntoResponse for AppError {
+    fn into_response(self) -> Response {
+        match &self {
+            AppError::DBError(error) => error!("{:?}", error),
+            AppError::ViolatedAssertion(assertion) => error!("{}", assertion),
+            _ => {}
+        }
+
+        let status = self.status();
+
+        // Details of internal errors stay in the logs
+        let message = match status {
+            StatusCode::INTERNAL_SERVER_ERROR => "Internal server error".to_string(),
+            _ => self.to_string(),
         };
 
-        status.into_response()
+        let body = Json(ErrorResponse {
+            code: self.code(),
+            message,
+            details: self.details(),
+            request_id: current_request_id(),
+        });
+
+        match self {
+            AppError::RateLimited { retry_after } => {
+                (status, [(RETRY_AFTER, retry_after.to_string())], body).into_response()
+            }
+            _ => (status, body).into_response(),
+        }
+    }
+}
+
+impl From<JsonRejection> for AppError {
+    fn from(rejection: JsonRejection) -> Self {
+        AppError::Validation {
+            field: "body",
+            message: rejection_message(&rejection),
+        }
+    }
+}
+
+impl From<PathRejection> for AppError {
+    fn from(rejection: PathRejection) -> Self {
+        AppError::Validation {
+            field: "path",
+            message: rejection_message(&rejection),
+        }
+    }
+}
+
+impl From<QueryRejection> for AppError {
+    fn from(rejection: QueryRejection) -> Self {
+        AppError::Validation {
+            field: "query",
+            message: rejection_message(&rejection),
+        }
     }
 }
+
+/// Message of an extractor rejection including its causes, e.g. the field that failed to
+/// deserialize
+pub fn rejection_message(rejection: &dyn std::error::Error) -> String {
+    let mut message = rejection.to_string();
+    let mut source = rejection.source();
+
+    while let Some(cause) = source {
+        let cause_message = cause.to_string();
+        if !message.ends_with(&cause_message) {
+            message = format!("{}: {}", message, cause_message);
+        }
+        source = cause.source();
+    }
+
+    message
+}
@@ -0,0 +1,239 @@
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use hyper::StatusCode;
+use serde::Serialize;
+use sqlx::{query_scalar, PgPool};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+use tokio::time::timeout;
+use tracing::warn;
+use utoipa::ToSchema;
+
+use crate::{config::SharedConfig, migrations::migrations_current, shutdown::Shutdown};
+
+/// Maximum time the readiness checks wait for the database: 2 seconds
+const DATABASE_TIMEOUT_SECONDS: u64 = 2;
+
+/// Name of the heartbeat of the notes deletion schedule
+pub const NOTES_DELETION: &str = "notes_deletion";
+
+/// Name of the heartbeat of the tokens deletion schedule
+pub const TOKENS_DELETION: &str = "tokens_deletion";
+
+/// Name of the heartbeat of the users deletion schedule
+pub const USERS_DELETION: &str = "users_deletion";
+
+/// Last time each background schedule was seen running
+#[derive(Clone, Default)]
+pub struct Heartbeats {
+    beats: Arc<Mutex<BTreeMap<&'static str, DateTime<Utc>>>>,
+}
+
+impl Heartbeats {
+    /// Record that a schedule is running
+    pub fn beat(&self, schedule: &'static str) {
+        self.beats
+            .lock()
+            .expect("heartbeats poisoned")
+            .insert(schedule, Utc::now());
+    }
+
+    fn last(&self, schedule: &str) -> Option<DateTime<Utc>> {
+        self.beats
+            .lock()
+            .expect("heartbeats poisoned")
+            .get(schedule)
+            .copied()
+    }
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct HealthResponse {
+    status: &'static str,
+}
+
+/// Liveness, answers as long as the process serves requests
+#[utoipa::path(
+    get,
+    path = "/healthz",
+    tag = "health",
+    responses((status = 200, description = "Process is alive", body = HealthResponse)),
+)]
+pub async fn healthz_handler() -> Json<HealthResponse> {
+    Json(HealthResponse { status: "ok" })
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ReadinessResponse {
+    status: &'static str,
+    database: DatabaseCheck,
+    migrations: Check,
+    schedules: BTreeMap<&'static str, ScheduleCheck>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct Check {
+    ok: bool,
+    error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct DatabaseCheck {
+    ok: bool,
+    latency_ms: Option<u64>,
+    error: Option<String>,
+}
+
+#[derive(Serialize, ToSchema)]
+pub struct ScheduleCheck {
+    ok: bool,
+    last_heartbeat: Option<DateTime<Utc>>,
+}
+
+/// Readiness, the database has to be reachable and current and all schedules running.
+/// Not ready while shutting down.
+#[utoipa::path(
+    get,
+    path = "/readyz",
+    tag = "health",
+    responses(
+        (status = 200, description = "Ready to serve traffic", body = ReadinessResponse),
+        (status = 503, description = "Not ready or shutting down", body = ReadinessResponse),
+    ),
+)]
+pub async fn readyz_handler(
+    db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(heartbeats): Extension<Heartbeats>,
+    Extension(shutdown): Extension<Shutdown>,
+) -> Response {
+    let database = check_database(&db).await;
+
+    let migrations = if database.ok {
+        check_migrations(&db).await
+    } else {
+        Check {
+            ok: false,
+            error: Some("Database unreachable".to_string()),
+        }
+    };
+
+    let now = Utc::now();
+    let schedules: BTreeMap<_, _> = [
+        (NOTES_DELETION, config.schedules.notes_deletion),
+        (TOKENS_DELETION, config.schedules.tokens_deletion),
+        (USERS_DELETION, config.schedules.users_deletion),
+    ]
+    .into_iter()
+    .map(|(schedule, interval)| {
+        let last_heartbeat = heartbeats.last(schedule);
+        let check = ScheduleCheck {
+            ok: heartbeat_fresh(last_heartbeat, interval, now),
+            last_heartbeat,
+        };
+        (schedule, check)
+    })
+    .collect();
+
+    let ready = database.ok && migrations.ok && schedules.values().all(|check| check.ok);
+
+    // Draining instances shouldn't receive new traffic
+    let (status_code, status) = if shutdown.is_requested() {
+        (StatusCode::SERVICE_UNAVAILABLE, "shutting_down")
+    } else if ready {
+        (StatusCode::OK, "ready")
+    } else {
+        warn!("Readiness check failed");
+        (StatusCode::SERVICE_UNAVAILABLE, "not_ready")
+    };
+
+    let response = ReadinessResponse {
+        status,
+        database,
+        migrations,
+        schedules,
+    };
+
+    (status_code, Json(response)).into_response()
+}
+
+async fn check_database(db: &PgPool) -> DatabaseCheck {
+    let start = Instant::now();
+
+    match timeout(
+        std::time::Duration::from_secs(DATABASE_TIMEOUT_SECONDS),
+        query_scalar::<_, i32>("SELECT 1;").fetch_one(db),
+    )
+    .await
+    {
+        Ok(Ok(_)) => DatabaseCheck {
+            ok: true,
+            latency_ms: Some(start.elapsed().as_millis() as u64),
+            error: None,
+        },
+        Ok(Err(err)) => {
+            // Details of the error stay in the logs, the endpoint is unauthenticated
+            warn!("Readiness database check failed: {:?}", err);
+
+            DatabaseCheck {
+                ok: false,
+                latency_ms: None,
+                error: Some("Database unreachable".to_string()),
+            }
+        }
+        Err(_) => DatabaseCheck {
+            ok: false,
+            latency_ms: None,
+            error: Some("Timed out".to_string()),
+        },
+    }
+}
+
+async fn check_migrations(db: &PgPool) -> Check {
+    match timeout(
+        std::time::Duration::from_secs(DATABASE_TIMEOUT_SECONDS),
+        migrations_current(db),
+    )
+    .await
+    {
+        Ok(Ok(true)) => Check {
+            ok: true,
+            error: None,
+        },
+        Ok(Ok(false)) => Check {
+            ok: false,
+            error: Some("Schema differs from binary".to_string()),
+        },
+        Ok(Err(err)) => {
+            warn!("Readiness migration check failed: {:?}", err);
+
+            Check {
+                ok: false,
+                error: Some("Migration check failed".to_string()),
+            }
+        }
+        Err(_) => Check {
+            ok: false,
+            error: Some("Timed out".to_string()),
+        },
+    }
+}
+
+// Schedules beat when starting and on every run, allow missing one run before failing
+fn heartbeat_fresh(
+    last_heartbeat: Option<DateTime<Utc>>,
+    interval: Duration,
+    now: DateTime<Utc>,
+) -> bool {
+    match last_heartbeat {
+        Some(last_heartbeat) => now - last_heartbeat < interval * 2,
+        None => false,
+    }
+}
@@ -0,0 +1,128 @@
+use crate::{
+    authentication::{is_admin, AuthenticatedUser},
+    error::AppError,
+    extract::Json,
+    util::{get_expires_at, get_invite_code, hash_token},
+};
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+/// Uses allowed for invites created by users other than admins
+const MAX_USER_INVITE_USES: i32 = 5;
+
+/// Request to create a signup invite
+#[derive(Deserialize, ToSchema)]
+pub struct CreateInviteRequest {
+    /// Number of signups the invite allows, at most 5 unless created by an admin
+    max_uses: Option<i32>,
+    /// Hours until the invite expires, at most 8760. Only admins can create invites that
+    /// never expire.
+    expires_in: Option<i64>,
+}
+
+/// Response to create invite, the only time the code itself is returned
+#[derive(Serialize, ToSchema)]
+pub struct CreateInviteResponse {
+    id: i32,
+    code: String,
+    max_uses: i32,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Create a new invite code, single-use unless specified otherwise
+#[utoipa::path(
+    post,
+    path = "/invites",
+    tag = "invites",
+    request_body = CreateInviteRequest,
+    responses(
+        (status = 200, description = "Invite created, the code is only returned now", body = CreateInviteResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 422, description = "Invalid number of uses, expiration out of range or missing", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn create_invite_handler(
+    user: AuthenticatedUser,
+    Json(request): Json<CreateInviteRequest>,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    let max_uses = request.max_uses.unwrap_or(1);
+
+    if max_uses < 1 {
+        return Err(AppError::Validation {
+            field: "max_uses",
+            message: "Invite has to allow at least one use".to_string(),
+        });
+    }
+
+    let admin = is_admin(&user, &db).await?;
+
+    if !admin && max_uses > MAX_USER_INVITE_USES {
+        return Err(AppError::Validation {
+            field: "max_uses",
+            message: format!("Invite can allow at most {} uses", MAX_USER_INVITE_USES),
+        });
+    }
+
+    if !admin && request.expires_in.is_none() {
+        return Err(AppError::Validation {
+            field: "expires_in",
+            message: "Invite has to expire".to_string(),
+        });
+    }
+
+    let now = Utc::now();
+    let code = get_invite_code();
+    let expires_at = get_expires_at(now, request.expires_in)?;
+
+    let id = create_invite(
+        user.user_id,
+        &hash_token(&code),
+        max_uses,
+        now,
+        expires_at,
+        &db,
+    )
+    .await?;
+
+    Ok(Json(&CreateInviteResponse {
+        id,
+        code,
+        max_uses,
+        created_at: now,
+        expires_at,
+    })
+    .into_response())
+}
+
+async fn create_invite(
+    user_id: i32,
+    code_hash: &str,
+    max_uses: i32,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    db: &PgPool,
+) -> Result<i32, AppError> {
+    let row = query!(
+        "INSERT INTO invites (code, created_by, created_at, expires_at, max_uses)
+        VALUES ($1, $2, $3, $4, $5)
+        RETURNING id;",
+        code_hash,
+        user_id,
+        created_at,
+        expires_at,
+        max_uses,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.id)
+}
@@ -0,0 +1,59 @@
+use crate::{authentication::AuthenticatedUser, error::AppError};
+use axum::{extract::Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+/// List invites response
+#[derive(Serialize, ToSchema)]
+pub struct ListInviteResponse {
+    id: i32,
+    max_uses: i32,
+    uses: i32,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// List invites created by the user
+#[utoipa::path(
+    get,
+    path = "/invites",
+    tag = "invites",
+    responses(
+        (status = 200, description = "Invites created by the user", body = [ListInviteResponse]),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn list_invites_handler(
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Json<Vec<ListInviteResponse>>, AppError> {
+    let invites = list_invites(user.user_id, &db).await?;
+
+    Ok(Json(invites))
+}
+
+async fn list_invites(user_id: i32, db: &PgPool) -> Result<Vec<ListInviteResponse>, AppError> {
+    let rows = query!(
+        "SELECT id, max_uses, uses, created_at, expires_at
+        FROM invites
+        WHERE created_by = $1
+        ORDER BY created_at;",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|invite| ListInviteResponse {
+            id: invite.id,
+            max_uses: invite.max_uses,
+            uses: invite.uses,
+            created_at: invite.created_at,
+            expires_at: invite.expires_at,
+        })
+        .collect())
+}
@@ -0,0 +1,48 @@
+mod create_invite;
+mod list_invites;
+mod revoke_invite;
+
+pub use create_invite::create_invite_handler;
+pub use list_invites::list_invites_handler;
+pub use revoke_invite::revoke_invite_handler;
+
+use chrono::{DateTime, Utc};
+use sqlx::{query, Postgres, Transaction};
+use utoipa::OpenApi;
+
+use crate::{error::AppError, util::hash_token};
+
+/// OpenAPI document of the invite routes
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        create_invite::create_invite_handler,
+        list_invites::list_invites_handler,
+        revoke_invite::revoke_invite_handler,
+    ),
+    components(schemas(
+        create_invite::CreateInviteRequest,
+        create_invite::CreateInviteResponse,
+        list_invites::ListInviteResponse,
+    ))
+)]
+pub struct InvitesApi;
+
+/// Use up one use of the invite code. Returns whether the code was valid.
+pub async fn consume_invite(
+    code: &str,
+    now: DateTime<Utc>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<bool, AppError> {
+    let result = query!(
+        "UPDATE invites
+        SET uses = uses + 1
+        WHERE code = $1 AND uses < max_uses AND (expires_at IS NULL OR expires_at > $2);",
+        hash_token(code),
+        now,
+    )
+    .execute(tx)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
@@ -0,0 +1,49 @@
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use hyper::StatusCode;
+use sqlx::{query, PgPool};
+
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Path};
+
+/// Revoke an invite created by the user
+#[utoipa::path(
+    delete,
+    path = "/invites/{id}",
+    tag = "invites",
+    params(("id" = i32, Path, description = "Id of the invite")),
+    responses(
+        (status = 200, description = "Invite revoked"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Invite doesn't exist", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn revoke_invite_handler(
+    Path(id): Path<i32>,
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    revoke_invite(user.user_id, id, &db).await?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn revoke_invite(user_id: i32, id: i32, db: &PgPool) -> Result<(), AppError> {
+    let row = query!(
+        "DELETE
+        FROM invites
+        WHERE id = $1 AND created_by = $2;",
+        id,
+        user_id,
+    )
+    .execute(db)
+    .await?;
+
+    if row.rows_affected() == 1 {
+        Ok(())
+    } else {
+        Err(AppError::NotFound("Invite"))
+    }
+}
@@ -0,0 +1,122 @@
+use axum::async_trait;
+use std::{process::Stdio, str::FromStr, sync::Arc};
+use thiserror::Error;
+use tokio::{io::AsyncWriteExt, process::Command};
+use tracing::warn;
+
+/// Mailer shared between handlers
+pub type SharedMailer = Arc<dyn Mailer>;
+
+/// Plain text email sent to a user
+pub struct Mail {
+    pub to: String,
+    pub subject: String,
+    pub body: String,
+}
+
+#[derive(Error, Debug)]
+pub enum MailError {
+    #[error("Mail transport io error")]
+    Io(#[from] std::io::Error),
+
+    #[error("Mail transport rejected mail: {0}")]
+    Rejected(String),
+}
+
+/// Transport used to deliver emails to users
+#[async_trait]
+pub trait Mailer: Send + Sync {
+    async fn send(&self, mail: Mail) -> Result<(), MailError>;
+}
+
+/// Mailer that only writes mails to the log, used when no transport is configured
+pub struct LogMailer;
+
+#[async_trait]
+impl Mailer for LogMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        warn!(
+            "No mail transport configured, mail to {} with subject '{}':\n{}",
+            mail.to, mail.subject, mail.body
+        );
+
+        Ok(())
+    }
+}
+
+/// Mailer handing mails to a local sendmail compatible binary
+pub struct SendmailMailer {
+    path: String,
+    from: String,
+}
+
+#[async_trait]
+impl Mailer for SendmailMailer {
+    async fn send(&self, mail: Mail) -> Result<(), MailError> {
+        let mut child = Command::new(&self.path)
+            .args(["-t", "-i"])
+            .stdin(Stdio::piped())
+            .spawn()?;
+
+        let message = format!(
+            "From: {}\r\nTo: {}\r\nSubject: {}\r\nContent-Type: text/plain; charset=utf-8\r\n\r\n{}\r\n",
+            self.from, mail.to, mail.subject, mail.body
+        );
+
+        if let Some(mut stdin) = child.stdin.take() {
+            stdin.write_all(message.as_bytes()).await?;
+        }
+
+        let status = child.wait().await?;
+
+        if status.success() {
+            Ok(())
+        } else {
+            Err(MailError::Rejected(format!(
+                "sendmail exited with {}",
+                status
+            )))
+        }
+    }
+}
+
+/// Transport used to deliver mail
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum MailTransport {
+    /// Only log mails, for development
+    Log,
+    /// Hand mails to the local sendmail binary
+    Sendmail,
+}
+
+impl FromStr for MailTransport {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "log" => Ok(MailTransport::Log),
+            "sendmail" => Ok(MailTransport::Sendmail),
+            _ => Err(format!("unknown mail transport {}", name)),
+        }
+    }
+}
+
+/// Mail delivery settings
+#[derive(Clone)]
+pub struct MailConfig {
+    pub transport: MailTransport,
+    pub sendmail_path: String,
+    /// Sender address, required for sendmail
+    pub from: Option<String>,
+}
+
+/// Create the mailer for the configured transport
+pub fn mailer_from_config(config: &MailConfig) -> SharedMailer {
+    match (config.transport, &config.from) {
+        (MailTransport::Sendmail, Some(from)) => Arc::new(SendmailMailer {
+            path: config.sendmail_path.clone(),
+            from: from.clone(),
+        }),
+        _ => Arc::new(LogMailer),
+    }
+}
@@ -1,119 +1,324 @@
+mod admin;
 mod authentication;
+mod cli;
+mod client_ip;
+mod config;
+mod csrf;
 mod error;
+mod extract;
+mod health;
+mod invites;
+mod mailer;
+mod metrics;
+mod migrations;
 mod notes;
+mod openapi;
+mod rate_limit;
+mod request_id;
+mod rotation;
 mod schedule;
 mod shares;
+mod shutdown;
+mod tokens;
 mod users;
 mod util;
 
+use authentication::{session_renewal, LoginThrottle, Scope};
 use axum::{
+    middleware,
     routing::{delete, get, post, put},
     Extension, Router, Server,
 };
+use clap::Parser;
+use cli::{Cli, Command};
+use config::{Config, LogFormat, SharedConfig};
+use csrf::{csrf_protection, CSRF_HEADER};
 use dotenv::dotenv;
-use hyper::{header::CONTENT_TYPE, Method};
-use log::{info, LevelFilter};
-use schedule::{notes_deletion_schedule, tokens_deletion_schedule};
-use simplelog::{ColorChoice, CombinedLogger, ConfigBuilder, TermLogger, TerminalMode};
-use sqlx::postgres::PgPoolOptions;
-use std::net::SocketAddr;
+use health::{healthz_handler, readyz_handler, Heartbeats};
+use hyper::{
+    header::{HeaderName, AUTHORIZATION, CONTENT_TYPE},
+    Method,
+};
+use mailer::mailer_from_config;
+use metrics::{metrics_server, track_metrics, Metrics};
+use migrations::{check_migrations, run_migrations};
+use openapi::openapi_handler;
+use rate_limit::{rate_limit, RateLimiter};
+use request_id::{request_context, REQUEST_ID_HEADER};
+use schedule::{notes_deletion_schedule, tokens_deletion_schedule, users_deletion_schedule};
+use shutdown::Shutdown;
+use sqlx::{postgres::PgPoolOptions, PgPool};
+use std::{net::SocketAddr, process, sync::Arc};
+use tokio::time::sleep;
 use tower_http::cors::{CorsLayer, Origin};
+use tracing::{error, info, warn};
 
 use crate::{
+    admin::{
+        admin_delete_user_handler, disable_user_handler, enable_user_handler, list_users_handler,
+        logout_user_handler,
+    },
+    invites::{create_invite_handler, list_invites_handler, revoke_invite_handler},
     notes::{
         delete_note_handler, get_note_handler, list_notes_handler, save_note_handler,
         undelete_note_handler, update_note_handler,
     },
+    rotation::{
+        abort_rotation_handler, begin_rotation_handler, commit_rotation_handler,
+        upload_keys_handler,
+    },
     shares::{
         access_share_handler, create_share_handler, delete_share_handler, list_shares_handler,
     },
+    tokens::{create_token_handler, list_tokens_handler, revoke_token_handler},
     users::{
-        change_password_handler, delete_user_handler, invalidate_sessions, login_handler,
-        logout_handler, signup_handler, store_salt_handler, user_info_handler,
+        access_recovery_material_handler, change_password_handler, confirm_password_reset_handler,
+        delete_user_handler, export_handler, get_recovery_material_handler, import_handler,
+        invalidate_sessions, list_sessions_handler, login_handler, logout_handler,
+        request_password_reset_handler, revoke_session_handler, signup_handler,
+        store_recovery_material_handler, store_salt_handler, user_info_handler,
     },
 };
 
 #[tokio::main]
 async fn main() {
-    CombinedLogger::init(vec![TermLogger::new(
-        LevelFilter::Warn,
-        ConfigBuilder::new()
-            .set_time_format_str("%F %T")
-            .set_time_to_local(true)
-            .build(),
-        TerminalMode::Mixed,
-        ColorChoice::Auto,
-    )])
-    .unwrap();
-
-    info!("Server started");
+    let cli = Cli::parse();
 
     dotenv().ok();
 
+    let config = match Config::load() {
+        Ok(config) => Arc::new(config),
+        Err(errors) => {
+            eprintln!("Invalid configuration:");
+            for error in errors {
+                eprintln!("  {}", error);
+            }
+            process::exit(1);
+        }
+    };
+
+    let subscriber = tracing_subscriber::fmt().with_max_level(config.log_level);
+    match config.log_format {
+        LogFormat::Text => subscriber.init(),
+        LogFormat::Json => subscriber.json().with_span_list(false).init(),
+    }
+
     let pool = PgPoolOptions::new()
-        .max_connections(100)
-        .connect(&dotenv::var("DATABASE_URL").expect("DATABASE_URL env variable missing"))
+        .max_connections(config.db_max_connections)
+        .connect(&config.database_url)
         .await
         .expect("DB connection failed");
 
+    match cli.command.unwrap_or(Command::Serve) {
+        Command::Serve => serve(config, pool).await,
+        command => {
+            if let Err(err) = cli::run(command, &config, &pool).await {
+                error!("{}", err);
+                process::exit(1);
+            }
+        }
+    }
+}
+
+async fn serve(config: SharedConfig, pool: PgPool) {
+    let migrated = if config.run_migrations {
+        run_migrations(&pool).await
+    } else {
+        check_migrations(&pool).await
+    };
+
+    if let Err(err) = migrated {
+        error!("{}", err);
+        process::exit(1);
+    }
+
+    info!("Server started");
+
     let db = pool.clone();
 
-    let write_origin = dotenv::var("WRITE_APP")
-        .expect("WRITE_APP env variable missing")
-        .as_str()
-        .parse()
-        .expect("WRITE_APP env variable malformed");
-    let read_origin = dotenv::var("READ_APP")
-        .expect("READ_APP env variable missing")
-        .as_str()
-        .parse()
-        .expect("READ_APP env variable malformed");
-    let read_www_origin = dotenv::var("READ_APP_WWW")
-        .expect("READ_APP_WWW env variable missing")
-        .as_str()
-        .parse()
-        .expect("READ_APP_WWW env variable malformed");
-
-    let origins = Origin::list(vec![write_origin, read_origin, read_www_origin]);
+    let origins = Origin::list(config.origins.iter().map(|origin| {
+        origin
+            .parse()
+            .expect("origins are validated when loading the config")
+    }));
+
+    let mailer = mailer_from_config(&config.mail);
+
+    let login_throttle = LoginThrottle::new(mailer.clone());
+
+    let rate_limiter = RateLimiter::new(config.rate_limits.clone());
+
+    let metrics = Metrics::new(config.db_max_connections);
+
+    let heartbeats = Heartbeats::default();
+
+    let shutdown = Shutdown::listen();
 
     let app = Router::new()
+        .route("/healthz", get(healthz_handler))
+        .route("/readyz", get(readyz_handler))
+        .route("/openapi.json", get(openapi_handler))
         .route("/user", post(signup_handler))
         .route("/session", post(login_handler))
         .route("/user", delete(delete_user_handler))
         .route("/user", put(change_password_handler))
         .route("/session", delete(logout_handler))
-        .route("/user/info", get(user_info_handler))
+        .route(
+            "/user/info",
+            get(user_info_handler).layer(Extension(Scope::NotesRead)),
+        )
         .route("/user/salt", put(store_salt_handler))
+        .route("/user/export", get(export_handler))
+        .route("/user/import", post(import_handler))
+        .route("/user/reset", post(request_password_reset_handler))
+        .route("/user/reset", put(confirm_password_reset_handler))
+        .route("/user/recovery", put(store_recovery_material_handler))
+        .route("/user/recovery", get(get_recovery_material_handler))
+        .route(
+            "/user/recovery/reset",
+            post(access_recovery_material_handler),
+        )
         .route("/allsessions", delete(invalidate_sessions))
-        .route("/notes", get(list_notes_handler))
-        .route("/notes/:token", get(get_note_handler))
-        .route("/notes", post(save_note_handler))
-        .route("/notes/:token", put(update_note_handler))
-        .route("/notes/:token", delete(delete_note_handler))
-        .route("/notes/undelete/:token", get(undelete_note_handler))
-        .route("/shares", post(create_share_handler))
-        .route("/shares", get(list_shares_handler))
-        .route("/shares/:token", delete(delete_share_handler))
+        .route("/sessions", get(list_sessions_handler))
+        .route("/sessions/:id", delete(revoke_session_handler))
+        .route(
+            "/notes",
+            get(list_notes_handler).layer(Extension(Scope::NotesRead)),
+        )
+        .route(
+            "/notes/:token",
+            get(get_note_handler).layer(Extension(Scope::NotesRead)),
+        )
+        .route(
+            "/notes",
+            post(save_note_handler).layer(Extension(Scope::NotesWrite)),
+        )
+        .route(
+            "/notes/:token",
+            put(update_note_handler).layer(Extension(Scope::NotesWrite)),
+        )
+        .route(
+            "/notes/:token",
+            delete(delete_note_handler).layer(Extension(Scope::NotesWrite)),
+        )
+        .route(
+            "/notes/undelete/:token",
+            post(undelete_note_handler).layer(Extension(Scope::NotesWrite)),
+        )
+        .route("/rotation", post(begin_rotation_handler))
+        .route("/rotation", delete(abort_rotation_handler))
+        .route("/rotation/keys", put(upload_keys_handler))
+        .route("/rotation/commit", post(commit_rotation_handler))
+        .route(
+            "/shares",
+            post(create_share_handler).layer(Extension(Scope::SharesManage)),
+        )
+        .route(
+            "/shares",
+            get(list_shares_handler).layer(Extension(Scope::SharesManage)),
+        )
+        .route(
+            "/shares/:token",
+            delete(delete_share_handler).layer(Extension(Scope::SharesManage)),
+        )
         .route("/shares/:token", get(access_share_handler))
+        .route("/tokens", post(create_token_handler))
+        .route("/tokens", get(list_tokens_handler))
+        .route("/tokens/:id", delete(revoke_token_handler))
+        .route("/invites", post(create_invite_handler))
+        .route("/invites", get(list_invites_handler))
+        .route("/invites/:id", delete(revoke_invite_handler))
+        .route("/admin/users", get(list_users_handler))
+        .route("/admin/users/:id", delete(admin_delete_user_handler))
+        .route("/admin/users/:id/disable", post(disable_user_handler))
+        .route("/admin/users/:id/enable", post(enable_user_handler))
+        .route("/admin/users/:id/sessions", delete(logout_user_handler))
+        .layer(middleware::from_fn(session_renewal))
+        .layer(middleware::from_fn(csrf_protection))
+        .layer(middleware::from_fn(rate_limit))
+        .layer(middleware::from_fn(track_metrics))
+        .layer(middleware::from_fn(request_context))
+        .layer(Extension(rate_limiter))
         .layer(Extension(db))
+        .layer(Extension(config.clone()))
+        .layer(Extension(mailer))
+        .layer(Extension(login_throttle))
+        .layer(Extension(metrics.clone()))
+        .layer(Extension(heartbeats.clone()))
+        .layer(Extension(shutdown.clone()))
         .layer(
             CorsLayer::new()
                 .allow_origin(origins)
                 .allow_methods(vec![Method::GET, Method::POST, Method::PUT, Method::DELETE])
                 .allow_credentials(true)
-                .allow_headers(vec![CONTENT_TYPE]),
+                .allow_headers(vec![
+                    CONTENT_TYPE,
+                    AUTHORIZATION,
+                    HeaderName::from_static(CSRF_HEADER),
+                    HeaderName::from_static(REQUEST_ID_HEADER),
+                ])
+                .expose_headers(vec![HeaderName::from_static(REQUEST_ID_HEADER)]),
+        );
+
+    // Stops accepting connections on shutdown and waits for in-flight requests
+    let server = Server::bind(&config.listen)
+        .serve(app.into_make_service_with_connect_info::<SocketAddr>())
+        .with_graceful_shutdown(shutdown.clone().requested());
+
+    let running = async {
+        let (served, _, _, _, _) = tokio::join!(
+            server,
+            metrics_server(
+                config.metrics_listen,
+                metrics.clone(),
+                pool.clone(),
+                shutdown.clone()
+            ),
+            notes_deletion_schedule(
+                pool.clone(),
+                config.clone(),
+                metrics.clone(),
+                heartbeats.clone(),
+                shutdown.clone()
+            ),
+            tokens_deletion_schedule(
+                pool.clone(),
+                config.clone(),
+                metrics.clone(),
+                heartbeats.clone(),
+                shutdown.clone()
+            ),
+            users_deletion_schedule(
+                pool.clone(),
+                config.clone(),
+                heartbeats.clone(),
+                shutdown.clone()
+            )
         );
 
-    let listen: SocketAddr = dotenv::var("LISTEN")
-        .expect("LISTEN env variable missing")
-        .parse()
-        .expect("Listen address invalid");
+        if let Err(err) = served {
+            error!("Server failed: {}", err);
+        }
+    };
+
+    // Whatever is still running after the timeout is cancelled
+    let drain_timeout = async {
+        shutdown.clone().requested().await;
+        sleep(
+            config
+                .shutdown_timeout
+                .to_std()
+                .expect("shutdown timeout negative"),
+        )
+        .await;
+    };
+
+    tokio::select! {
+        _ = running => {}
+        _ = drain_timeout => warn!("Shutdown timed out, cancelling remaining requests and jobs"),
+    }
 
-    let server = Server::bind(&listen).serve(app.into_make_service());
+    pool.close().await;
 
-    let (_, _, _) = tokio::join!(
-        server,
-        notes_deletion_schedule(pool.clone()),
-        tokens_deletion_schedule(pool.clone())
-    );
+    info!("Shutdown complete");
 }
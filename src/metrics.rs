@@ -0,0 +1,205 @@
+use axum::{
+    extract::{Extension, MatchedPath},
+    http::Request,
+    middleware::Next,
+    response::{IntoResponse, Response},
+    routing::get,
+    Router, Server,
+};
+use hyper::{header::CONTENT_TYPE, StatusCode};
+use prometheus::{
+    exponential_buckets, Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, Opts,
+    Registry, TextEncoder, TEXT_FORMAT,
+};
+use sqlx::PgPool;
+use std::{net::SocketAddr, time::Instant};
+
+use crate::shutdown::Shutdown;
+use tracing::{error, info};
+
+/// Route label of requests that didn't match any route
+const UNMATCHED_ROUTE: &str = "unmatched";
+
+/// Prometheus metrics of the server, cheap to clone
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Registry,
+    requests: IntCounterVec,
+    request_duration: HistogramVec,
+    pool_connections: IntGauge,
+    pool_idle_connections: IntGauge,
+    auth_failures: IntCounterVec,
+    share_accesses: IntCounterVec,
+    cleanup_rows: HistogramVec,
+}
+
+impl Metrics {
+    pub fn new(max_connections: u32) -> Self {
+        let registry = Registry::new_custom(Some("fieldnotes".to_string()), None)
+            .expect("metrics prefix invalid");
+
+        let requests = IntCounterVec::new(
+            Opts::new("http_requests_total", "HTTP requests handled"),
+            &["method", "route", "status"],
+        )
+        .expect("metric invalid");
+        let request_duration = HistogramVec::new(
+            HistogramOpts::new("http_request_duration_seconds", "Latency of HTTP requests"),
+            &["method", "route", "status"],
+        )
+        .expect("metric invalid");
+        let pool_connections = IntGauge::new("db_pool_connections", "Open database connections")
+            .expect("metric invalid");
+        let pool_idle_connections =
+            IntGauge::new("db_pool_idle_connections", "Idle database connections")
+                .expect("metric invalid");
+        let pool_max_connections = IntGauge::new(
+            "db_pool_max_connections",
+            "Maximum number of database connections",
+        )
+        .expect("metric invalid");
+        let auth_failures = IntCounterVec::new(
+            Opts::new("auth_failures_total", "Failed authentication attempts"),
+            &["kind"],
+        )
+        .expect("metric invalid");
+        let share_accesses = IntCounterVec::new(
+            Opts::new("share_accesses_total", "Accesses of shared notes"),
+            &["outcome"],
+        )
+        .expect("metric invalid");
+        let cleanup_rows = HistogramVec::new(
+            HistogramOpts::new(
+                "cleanup_rows_affected",
+                "Rows deleted by each run of a cleanup job",
+            )
+            .buckets(exponential_buckets(1.0, 4.0, 8).expect("buckets invalid")),
+            &["job"],
+        )
+        .expect("metric invalid");
+
+        pool_max_connections.set(max_connections.into());
+
+        for collector in [
+            Box::new(requests.clone()) as Box<dyn prometheus::core::Collector>,
+            Box::new(request_duration.clone()),
+            Box::new(pool_connections.clone()),
+            Box::new(pool_idle_connections.clone()),
+            Box::new(pool_max_connections.clone()),
+            Box::new(auth_failures.clone()),
+            Box::new(share_accesses.clone()),
+            Box::new(cleanup_rows.clone()),
+        ] {
+            registry
+                .register(collector)
+                .expect("metric registered twice");
+        }
+
+        Metrics {
+            registry,
+            requests,
+            request_duration,
+            pool_connections,
+            pool_idle_connections,
+            auth_failures,
+            share_accesses,
+            cleanup_rows,
+        }
+    }
+
+    /// Count a failed authentication, `kind` is the kind of credential
+    pub fn auth_failure(&self, kind: &str) {
+        self.auth_failures.with_label_values(&[kind]).inc();
+    }
+
+    /// Count an access of a shared note
+    pub fn share_access(&self, outcome: &str) {
+        self.share_accesses.with_label_values(&[outcome]).inc();
+    }
+
+    /// Record the rows deleted by a run of a cleanup job
+    pub fn cleanup(&self, job: &str, rows: u64) {
+        self.cleanup_rows
+            .with_label_values(&[job])
+            .observe(rows as f64);
+    }
+
+    // Text exposition of all metrics, sampling the pool at the time of the scrape
+    fn render(&self, db: &PgPool) -> Result<String, prometheus::Error> {
+        self.pool_connections.set(db.size().into());
+        self.pool_idle_connections.set(db.num_idle() as i64);
+
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+
+        Ok(String::from_utf8_lossy(&buffer).into_owned())
+    }
+}
+
+/// Record count and latency of requests by route and status
+pub async fn track_metrics<B>(req: Request<B>, next: Next<B>) -> Response {
+    let metrics = req.extensions().get::<Metrics>().cloned();
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string())
+        .unwrap_or_else(|| UNMATCHED_ROUTE.to_string());
+    let method = req.method().clone();
+
+    let start = Instant::now();
+    let response = next.run(req).await;
+
+    if let Some(metrics) = metrics {
+        let status = response.status();
+        let labels = [method.as_str(), route.as_str(), status.as_str()];
+
+        metrics.requests.with_label_values(&labels).inc();
+        metrics
+            .request_duration
+            .with_label_values(&labels)
+            .observe(start.elapsed().as_secs_f64());
+    }
+
+    response
+}
+
+/// Serve metrics on their own listen address, not reachable through the API
+pub async fn metrics_server(
+    listen: Option<SocketAddr>,
+    metrics: Metrics,
+    db: PgPool,
+    shutdown: Shutdown,
+) {
+    let listen = match listen {
+        Some(listen) => listen,
+        None => return,
+    };
+
+    let app = Router::new()
+        .route("/metrics", get(metrics_handler))
+        .layer(Extension(metrics))
+        .layer(Extension(db));
+
+    info!("Serving metrics on {}", listen);
+
+    let server = Server::bind(&listen)
+        .serve(app.into_make_service())
+        .with_graceful_shutdown(shutdown.requested());
+
+    if let Err(err) = server.await {
+        error!("Metrics server failed: {}", err);
+    }
+}
+
+async fn metrics_handler(
+    Extension(metrics): Extension<Metrics>,
+    Extension(db): Extension<PgPool>,
+) -> Response {
+    match metrics.render(&db) {
+        Ok(body) => ([(CONTENT_TYPE, TEXT_FORMAT)], body).into_response(),
+        Err(err) => {
+            error!("Rendering metrics failed: {}", err);
+            StatusCode::INTERNAL_SERVER_ERROR.into_response()
+        }
+    }
+}
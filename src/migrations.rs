@@ -0,0 +1,177 @@
+use sqlx::{
+    migrate::{Migrate, MigrateError, Migrator},
+    query, query_scalar, Connection, PgConnection, PgPool,
+};
+use std::collections::HashSet;
+use thiserror::Error;
+use tracing::{info, warn};
+
+/// Migrations embedded into the binary
+static MIGRATOR: Migrator = sqlx::migrate!("./db/migrations");
+
+#[derive(Error, Debug)]
+pub enum MigrationError {
+    #[error("Migration failed: {0}")]
+    Migrate(#[from] MigrateError),
+
+    #[error("Database error: {0}")]
+    Database(#[from] sqlx::Error),
+
+    #[error("Database schema version {0} is newer than this binary, refusing to start")]
+    SchemaNewer(i64),
+
+    #[error("{0} pending migrations, run the migrate command first")]
+    Pending(usize),
+}
+
+/// Apply all pending migrations
+pub async fn run_migrations(db: &PgPool) -> Result<(), MigrationError> {
+    let mut conn = db.acquire().await?;
+
+    // Held for the whole run, so concurrent replicas don't race
+    conn.lock().await?;
+    let result = apply_pending(&mut conn).await;
+    conn.unlock().await?;
+
+    result
+}
+
+/// Ensure the schema matches the binary without changing it
+pub async fn check_migrations(db: &PgPool) -> Result<(), MigrationError> {
+    let mut conn = db.acquire().await?;
+
+    conn.lock().await?;
+    let result = check_versions(&mut conn).await;
+    conn.unlock().await?;
+
+    match result? {
+        0 => Ok(()),
+        pending => Err(MigrationError::Pending(pending)),
+    }
+}
+
+async fn apply_pending(conn: &mut PgConnection) -> Result<(), MigrationError> {
+    let pending = check_versions(conn).await?;
+
+    if pending > 0 {
+        let migrator = Migrator {
+            migrations: MIGRATOR.migrations.clone(),
+            ignore_missing: false,
+            // The advisory lock is already held by the caller
+            locking: false,
+        };
+        migrator.run_direct(conn).await?;
+
+        warn!("Applied {} migrations", pending);
+    }
+
+    Ok(())
+}
+
+// Count pending migrations, failing if the database has migrations this binary doesn't know
+async fn check_versions(conn: &mut PgConnection) -> Result<usize, MigrationError> {
+    conn.ensure_migrations_table().await?;
+    adopt_dbmate_history(conn).await?;
+
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+    let known = known_versions();
+
+    if let Some(version) = applied.difference(&known).max() {
+        return Err(MigrationError::SchemaNewer(*version));
+    }
+
+    Ok(known.difference(&applied).count())
+}
+
+/// Whether the database is at the schema of this binary, without locking or changing anything
+pub async fn migrations_current(db: &PgPool) -> Result<bool, MigrationError> {
+    let mut conn = db.acquire().await?;
+
+    let applied: HashSet<i64> = conn
+        .list_applied_migrations()
+        .await?
+        .iter()
+        .map(|migration| migration.version)
+        .collect();
+
+    Ok(applied == known_versions())
+}
+
+fn known_versions() -> HashSet<i64> {
+    MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .map(|migration| migration.version)
+        .collect()
+}
+
+// Databases set up with dbmate record applied migrations in `schema_migrations`. Mark them
+// as applied for sqlx once, so they aren't run again.
+async fn adopt_dbmate_history(conn: &mut PgConnection) -> Result<(), MigrationError> {
+    if !conn.list_applied_migrations().await?.is_empty() {
+        return Ok(());
+    }
+
+    let has_dbmate_table =
+        query_scalar::<_, bool>("SELECT to_regclass('schema_migrations') IS NOT NULL;")
+            .fetch_one(&mut *conn)
+            .await?;
+
+    if !has_dbmate_table {
+        return Ok(());
+    }
+
+    // Unchecked queries, neither table exists when preparing the query data
+    let versions: HashSet<String> =
+        query_scalar::<_, String>("SELECT version FROM schema_migrations;")
+            .fetch_all(&mut *conn)
+            .await?
+            .into_iter()
+            .collect();
+
+    let known: HashSet<String> = known_versions()
+        .iter()
+        .map(|version| version.to_string())
+        .collect();
+
+    // Left over versions were applied by a newer release. Checked before adopting anything,
+    // as adoption is skipped once sqlx has a history.
+    if let Some(version) = versions
+        .difference(&known)
+        .filter_map(|version| version.parse().ok())
+        .max()
+    {
+        return Err(MigrationError::SchemaNewer(version));
+    }
+
+    let migrations: Vec<_> = MIGRATOR
+        .iter()
+        .filter(|migration| !migration.migration_type.is_down_migration())
+        .filter(|migration| versions.contains(&migration.version.to_string()))
+        .collect();
+
+    let mut tx = conn.begin().await?;
+
+    for migration in &migrations {
+        query(
+            "INSERT INTO _sqlx_migrations (version, description, success, checksum, execution_time)
+            VALUES ($1, $2, TRUE, $3, 0);",
+        )
+        .bind(migration.version)
+        .bind(&*migration.description)
+        .bind(&*migration.checksum)
+        .execute(&mut tx)
+        .await?;
+    }
+
+    tx.commit().await?;
+
+    info!("Adopted {} migrations applied by dbmate", migrations.len());
+
+    Ok(())
+}
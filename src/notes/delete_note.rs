@@ -1,14 +1,30 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     response::{IntoResponse, Response},
 };
 use chrono::{DateTime, Utc};
 use hyper::StatusCode;
 use sqlx::{query, PgPool};
 
-use crate::{authentication::AuthenticatedUser, error::AppError};
+use crate::{
+    authentication::AuthenticatedUser, error::AppError, extract::Path,
+    rotation::ensure_no_active_rotation,
+};
 
 /// Delete an existing note
+#[utoipa::path(
+    delete,
+    path = "/notes/{token}",
+    tag = "notes",
+    params(("token" = String, Path, description = "Token of the note")),
+    responses(
+        (status = 200, description = "Note moved to the trash"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Note doesn't exist", body = ErrorResponse),
+        (status = 409, description = "Key rotation in progress", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["notes:write"])),
+)]
 pub async fn delete_note_handler(
     Path(token): Path<String>,
     user: AuthenticatedUser,
@@ -29,6 +45,8 @@ async fn delete_note(
 ) -> Result<(), AppError> {
     let mut tx = db.begin().await?;
 
+    ensure_no_active_rotation(user_id, &mut tx).await?;
+
     let result = query!(
         "UPDATE notes
         SET deleted_at = $1
@@ -60,6 +78,6 @@ async fn delete_note(
         Ok(())
     } else {
         tx.rollback().await?;
-        Err(AppError::Unauthorized)
+        Err(AppError::NotFound("Note"))
     }
 }
@@ -1,16 +1,17 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     response::{IntoResponse, Response},
     Json,
 };
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
-use crate::{authentication::AuthenticatedUser, error::AppError};
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Path};
 
 /// Response to get note request
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct GetNoteResponse {
     id: String,
     modified_at: DateTime<Utc>,
@@ -21,6 +22,18 @@ pub struct GetNoteResponse {
 }
 
 /// Get an existing note
+#[utoipa::path(
+    get,
+    path = "/notes/{token}",
+    tag = "notes",
+    params(("token" = String, Path, description = "Token of the note")),
+    responses(
+        (status = 200, description = "Note", body = GetNoteResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Note doesn't exist", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["notes:read"])),
+)]
 pub async fn get_note_handler(
     Path(token): Path<String>,
     user: AuthenticatedUser,
@@ -50,6 +63,6 @@ async fn get_note(user_id: i32, token: &str, db: &PgPool) -> Result<GetNoteRespo
             key: row.key,
             content: row.content,
         }),
-        None => Err(AppError::Unauthorized),
+        None => Err(AppError::NotFound("Note")),
     }
 }
@@ -1,5 +1,5 @@
 use axum::{
-    extract::{Extension, Query},
+    extract::Extension,
     response::{IntoResponse, Response},
     Json,
 };
@@ -8,11 +8,12 @@ use serde::Serialize;
 use sqlx::{query, PgPool};
 use std::collections::HashMap;
 use tokio_stream::StreamExt;
+use utoipa::ToSchema;
 
-use crate::{authentication::AuthenticatedUser, error::AppError};
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Query};
 
 /// Response to list notes request
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ListNoteResponse {
     id: String,
     modified_at: DateTime<Utc>,
@@ -22,7 +23,7 @@ pub struct ListNoteResponse {
 }
 
 /// Response to list notes request
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ListDeletedNoteResponse {
     id: String,
     modified_at: DateTime<Utc>,
@@ -33,12 +34,23 @@ pub struct ListDeletedNoteResponse {
 }
 
 /// List all non-deleted notes
+#[utoipa::path(
+    get,
+    path = "/notes",
+    tag = "notes",
+    params(("deleted" = Option<String>, Query, description = "List deleted notes instead, the value is ignored")),
+    responses(
+        (status = 200, description = "Notes without content, as ListDeletedNoteResponse if deleted notes are listed", body = [ListNoteResponse]),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["notes:read"])),
+)]
 pub async fn list_notes_handler(
     Query(queries): Query<HashMap<String, String>>,
     user: AuthenticatedUser,
     db: Extension<PgPool>,
 ) -> Result<Response, AppError> {
-    if queries.get("deleted").is_some() {
+    if queries.contains_key("deleted") {
         let notes = list_deleted_notes(user.user_id, &db).await?;
 
         Ok(Json(notes).into_response())
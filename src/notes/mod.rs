@@ -11,3 +11,29 @@ pub use list_notes::list_notes_handler;
 pub use save_note::save_note_handler;
 pub use undelete_note::undelete_note_handler;
 pub use update_note::update_note_handler;
+
+use utoipa::OpenApi;
+
+/// OpenAPI document of the notes routes
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        list_notes::list_notes_handler,
+        get_note::get_note_handler,
+        save_note::save_note_handler,
+        update_note::update_note_handler,
+        delete_note::delete_note_handler,
+        undelete_note::undelete_note_handler,
+    ),
+    components(schemas(
+        list_notes::ListNoteResponse,
+        list_notes::ListDeletedNoteResponse,
+        get_note::GetNoteResponse,
+        save_note::SaveNoteRequest,
+        save_note::SaveNoteResponse,
+        update_note::UpdateNoteRequest,
+        update_note::UpdateNoteResponse,
+        undelete_note::UndeleteNoteResponse,
+    ))
+)]
+pub struct NotesApi;
@@ -1,15 +1,18 @@
-use crate::{authentication::AuthenticatedFundedUser, error::AppError, util::get_note_token};
+use crate::{
+    authentication::AuthenticatedFundedUser, error::AppError, extract::Json,
+    rotation::ensure_no_active_rotation, util::get_note_token,
+};
 use axum::{
     extract::Extension,
     response::{IntoResponse, Response},
-    Json,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
 /// Request to save note
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SaveNoteRequest {
     metadata: String,
     key: String,
@@ -17,7 +20,7 @@ pub struct SaveNoteRequest {
 }
 
 /// Response to save note
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct SaveNoteResponse {
     id: String,
     modified_at: DateTime<Utc>,
@@ -25,6 +28,18 @@ pub struct SaveNoteResponse {
 }
 
 /// Save a new note
+#[utoipa::path(
+    post,
+    path = "/notes",
+    tag = "notes",
+    request_body = SaveNoteRequest,
+    responses(
+        (status = 200, description = "Note saved", body = SaveNoteResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 409, description = "Key rotation in progress", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["notes:write"])),
+)]
 pub async fn save_note_handler(
     user: AuthenticatedFundedUser,
     Json(note): Json<SaveNoteRequest>,
@@ -39,6 +54,10 @@ pub async fn save_note_handler(
         content,
     } = note;
 
+    let mut tx = db.begin().await?;
+
+    ensure_no_active_rotation(user.user_id, &mut tx).await?;
+
     query!(
         "INSERT INTO notes (token, user_id, created_at, modified_at, metadata, key, content)
         VALUES ($1, $2, $3, $4, $5, $6, $7);",
@@ -50,13 +69,15 @@ pub async fn save_note_handler(
         key,
         content,
     )
-    .execute(&*db)
+    .execute(&mut tx)
     .await?;
 
+    tx.commit().await?;
+
     Ok(Json(&SaveNoteResponse {
         id: token.clone(),
         modified_at: now,
         created_at: now,
     })
     .into_response())
-}
\ No newline at end of file
+}
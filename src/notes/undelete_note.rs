@@ -1,16 +1,20 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     response::{IntoResponse, Response},
     Json,
 };
 use chrono::{DateTime, Utc};
 use serde::Serialize;
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
-use crate::{authentication::AuthenticatedUser, error::AppError};
+use crate::{
+    authentication::AuthenticatedUser, error::AppError, extract::Path,
+    rotation::ensure_no_active_rotation,
+};
 
 /// Response to get note request
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UndeleteNoteResponse {
     id: String,
     modified_at: DateTime<Utc>,
@@ -21,6 +25,19 @@ pub struct UndeleteNoteResponse {
 }
 
 /// Undelete an existing note
+#[utoipa::path(
+    post,
+    path = "/notes/undelete/{token}",
+    tag = "notes",
+    params(("token" = String, Path, description = "Token of the note")),
+    responses(
+        (status = 200, description = "Note restored from the trash", body = UndeleteNoteResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Note doesn't exist", body = ErrorResponse),
+        (status = 409, description = "Key rotation in progress", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["notes:write"])),
+)]
 pub async fn undelete_note_handler(
     Path(token): Path<String>,
     user: AuthenticatedUser,
@@ -36,7 +53,11 @@ async fn undelete_note(
     token: &str,
     db: &PgPool,
 ) -> Result<UndeleteNoteResponse, AppError> {
-    match query!(
+    let mut tx = db.begin().await?;
+
+    ensure_no_active_rotation(user_id, &mut tx).await?;
+
+    let note = match query!(
         "UPDATE notes
         SET deleted_at = NULL
         WHERE user_id = $1 AND token = $2 AND deleted_at IS NOT NULL
@@ -44,17 +65,21 @@ async fn undelete_note(
         user_id,
         token,
     )
-    .fetch_optional(db)
+    .fetch_optional(&mut tx)
     .await?
     {
-        Some(row) => Ok(UndeleteNoteResponse {
+        Some(row) => UndeleteNoteResponse {
             id: token.to_string(),
             modified_at: row.modified_at,
             created_at: row.created_at,
             metadata: row.metadata,
             key: row.key,
             content: row.content,
-        }),
-        None => Err(AppError::Unauthorized),
-    }
+        },
+        None => return Err(AppError::NotFound("Note")),
+    };
+
+    tx.commit().await?;
+
+    Ok(note)
 }
@@ -1,16 +1,21 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     response::{IntoResponse, Response},
-    Json,
 };
 use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
-use crate::{authentication::AuthenticatedFundedUser, error::AppError};
+use crate::{
+    authentication::AuthenticatedFundedUser,
+    error::AppError,
+    extract::{Json, Path},
+    rotation::ensure_no_active_rotation,
+};
 
 /// Request to save note
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UpdateNoteRequest {
     metadata: String,
     key: String,
@@ -18,13 +23,27 @@ pub struct UpdateNoteRequest {
 }
 
 /// Response to update note
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UpdateNoteResponse {
     id: String,
     modified_at: DateTime<Utc>,
 }
 
 /// Update an existing note
+#[utoipa::path(
+    put,
+    path = "/notes/{token}",
+    tag = "notes",
+    params(("token" = String, Path, description = "Token of the note")),
+    request_body = UpdateNoteRequest,
+    responses(
+        (status = 200, description = "Note updated", body = UpdateNoteResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Note doesn't exist", body = ErrorResponse),
+        (status = 409, description = "Key rotation in progress", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["notes:write"])),
+)]
 pub async fn update_note_handler(
     Path(token): Path<String>,
     user: AuthenticatedFundedUser,
@@ -57,6 +76,10 @@ async fn update_note(
     content: &str,
     db: &PgPool,
 ) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+
+    ensure_no_active_rotation(user_id, &mut tx).await?;
+
     let result = query!(
         "UPDATE notes
         SET modified_at = $1, metadata = $2, key = $3, content = $4
@@ -68,12 +91,14 @@ async fn update_note(
         user_id,
         token,
     )
-    .execute(db)
+    .execute(&mut tx)
     .await?;
 
     if result.rows_affected() == 1 {
+        tx.commit().await?;
         Ok(())
     } else {
-        Err(AppError::Unauthorized)
+        tx.rollback().await?;
+        Err(AppError::NotFound("Note"))
     }
 }
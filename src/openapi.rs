@@ -0,0 +1,118 @@
+use axum::Json;
+use utoipa::{
+    openapi::{
+        security::{ApiKey, ApiKeyValue, HttpAuthScheme, HttpBuilder, SecurityScheme},
+        OpenApi as OpenApiDocument,
+    },
+    Modify, OpenApi,
+};
+
+use crate::{
+    admin::AdminApi, error::ErrorResponse, health, invites::InvitesApi, notes::NotesApi,
+    rotation::RotationApi, shares::SharesApi, tokens::TokensApi, users::UsersApi,
+};
+
+/// Name of the security scheme of session cookies
+const SESSION_SECURITY: &str = "session";
+
+/// Name of the security scheme of API tokens
+const API_TOKEN_SECURITY: &str = "api_token";
+
+#[derive(OpenApi)]
+#[openapi(
+    info(
+        title = "fieldnotes API",
+        description = "Errors are answered with an ErrorResponse. All routes are rate limited \
+            and answer with 429 and a Retry-After header when the limit is exceeded."
+    ),
+    paths(health::healthz_handler, health::readyz_handler),
+    components(schemas(
+        ErrorResponse,
+        health::HealthResponse,
+        health::ReadinessResponse,
+        health::Check,
+        health::DatabaseCheck,
+        health::ScheduleCheck,
+    )),
+    modifiers(&SecuritySchemes),
+)]
+struct ApiDoc;
+
+struct SecuritySchemes;
+
+impl Modify for SecuritySchemes {
+    fn modify(&self, openapi: &mut OpenApiDocument) {
+        let components = openapi.components.get_or_insert_with(Default::default);
+
+        // Clients using a custom cookie name have to adapt the name
+        components.add_security_scheme(
+            SESSION_SECURITY,
+            SecurityScheme::ApiKey(ApiKey::Cookie(ApiKeyValue::with_description(
+                "token",
+                "Session token set by logging in",
+            ))),
+        );
+        components.add_security_scheme(
+            API_TOKEN_SECURITY,
+            SecurityScheme::Http(
+                HttpBuilder::new()
+                    .scheme(HttpAuthScheme::Bearer)
+                    .description(Some("Personal API token, limited to its scopes"))
+                    .build(),
+            ),
+        );
+    }
+}
+
+/// OpenAPI document of all routes, assembled from the documents of each module
+pub fn api_doc() -> OpenApiDocument {
+    let mut doc = ApiDoc::openapi();
+    // Taken from the package metadata, which doesn't declare a license
+    doc.info.license = None;
+
+    for module in [
+        UsersApi::openapi(),
+        NotesApi::openapi(),
+        RotationApi::openapi(),
+        SharesApi::openapi(),
+        TokensApi::openapi(),
+        InvitesApi::openapi(),
+        AdminApi::openapi(),
+    ] {
+        doc.merge(module);
+    }
+
+    doc
+}
+
+/// Serve the OpenAPI document
+pub async fn openapi_handler() -> Json<OpenApiDocument> {
+    Json(api_doc())
+}
+
+#[cfg(test)]
+mod tests {
+    use std::{env, fs};
+
+    use super::openapi_handler;
+
+    /// Checked-in snapshot of the served document, regenerate with `UPDATE_OPENAPI=1 cargo test`
+    const SNAPSHOT: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/openapi.json");
+
+    #[tokio::test]
+    async fn served_document_matches_snapshot() {
+        let served = openapi_handler().await.0.to_pretty_json().unwrap() + "\n";
+
+        if env::var_os("UPDATE_OPENAPI").is_some() {
+            fs::write(SNAPSHOT, &served).unwrap();
+        }
+
+        let snapshot = fs::read_to_string(SNAPSHOT).unwrap_or_default();
+
+        assert!(
+            served == snapshot,
+            "OpenAPI document differs from openapi.json, review the changes and regenerate \
+            it with `UPDATE_OPENAPI=1 cargo test`"
+        );
+    }
+}
@@ -0,0 +1,438 @@
+use axum::{
+    extract::{FromRequest, RequestParts},
+    http::{HeaderMap, HeaderValue, Method, Request, StatusCode},
+    middleware::Next,
+    response::{IntoResponse, Response},
+};
+use ring::digest::{digest, SHA256};
+use std::{
+    collections::{BTreeSet, HashMap},
+    net::{IpAddr, Ipv6Addr},
+    sync::{Arc, Mutex},
+    time::Instant,
+};
+
+use crate::{
+    client_ip::ClientIp, config::SharedConfig, error::AppError, util::get_token_from_header,
+};
+
+/// Number of buckets above which the least recently updated ones are dropped
+const MAX_TRACKED_BUCKETS: usize = 10_000;
+
+/// Groups of routes sharing a limit
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+pub enum RouteGroup {
+    /// Signup, login and password reset, keyed by client address
+    Auth,
+    /// Public access to shared notes, keyed by client address
+    Shares,
+    /// Everything else, keyed by session or API token if authenticated
+    Api,
+}
+
+impl RouteGroup {
+    fn of(method: &Method, path: &str) -> Self {
+        match (method, path) {
+            (&Method::POST, "/user" | "/session" | "/user/reset" | "/user/recovery/reset")
+            | (&Method::PUT, "/user/reset") => RouteGroup::Auth,
+            (&Method::GET, path) if path.starts_with("/shares/") => RouteGroup::Shares,
+            _ => RouteGroup::Api,
+        }
+    }
+}
+
+/// Token bucket parameters of a route group
+#[derive(Clone, Copy)]
+pub struct Limit {
+    /// Requests allowed in a burst
+    pub burst: u32,
+    /// Requests replenished per minute
+    pub per_minute: u32,
+}
+
+impl Limit {
+    fn refill_per_second(&self) -> f64 {
+        self.per_minute as f64 / 60.0
+    }
+}
+
+/// Limits of all route groups
+#[derive(Clone)]
+pub struct RateLimits {
+    pub auth: Limit,
+    pub shares: Limit,
+    pub api: Limit,
+}
+
+impl RateLimits {
+    fn get(&self, group: RouteGroup) -> Limit {
+        match group {
+            RouteGroup::Auth => self.auth,
+            RouteGroup::Shares => self.shares,
+            RouteGroup::Api => self.api,
+        }
+    }
+}
+
+/// Client a bucket belongs to
+#[derive(Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Debug)]
+enum ClientKey {
+    /// SHA-256 digest of the session or API token
+    Token([u8; 32]),
+    Ip(IpAddr),
+}
+
+impl ClientKey {
+    fn of_token(token: &str) -> Self {
+        let mut key = [0; 32];
+        key.copy_from_slice(digest(&SHA256, token.as_bytes()).as_ref());
+        ClientKey::Token(key)
+    }
+
+    // IPv6 clients usually get a whole /64, so they could switch addresses within it
+    fn of_ip(ip: IpAddr) -> Self {
+        match ip {
+            IpAddr::V6(ip) => match ip.to_ipv4_mapped() {
+                Some(ip) => ClientKey::Ip(IpAddr::V4(ip)),
+                None => {
+                    let [a, b, c, d, ..] = ip.segments();
+                    ClientKey::Ip(IpAddr::V6(Ipv6Addr::new(a, b, c, d, 0, 0, 0, 0)))
+                }
+            },
+            ip => ClientKey::Ip(ip),
+        }
+    }
+}
+
+type BucketKey = (RouteGroup, ClientKey);
+
+struct Bucket {
+    tokens: f64,
+    updated_at: Instant,
+}
+
+impl Bucket {
+    fn refill(&mut self, limit: Limit, now: Instant) {
+        self.tokens = self.tokens_at(limit, now);
+        self.updated_at = now;
+    }
+
+    fn tokens_at(&self, limit: Limit, now: Instant) -> f64 {
+        let elapsed = now.duration_since(self.updated_at).as_secs_f64();
+        (self.tokens + elapsed * limit.refill_per_second()).min(limit.burst as f64)
+    }
+}
+
+/// Buckets of all clients, indexed by the time they were last updated
+#[derive(Default)]
+struct Buckets {
+    by_key: HashMap<BucketKey, Bucket>,
+    by_age: BTreeSet<(Instant, BucketKey)>,
+}
+
+impl Buckets {
+    // Buckets that refilled completely are the same as new ones and can be dropped. Only the
+    // oldest are checked, which keeps the work per request constant on average.
+    fn drop_full(&mut self, limits: &RateLimits, now: Instant) {
+        while let Some(&(updated_at, key)) = self.by_age.iter().next() {
+            let limit = limits.get(key.0);
+            let bucket = &self.by_key[&key];
+
+            if bucket.tokens_at(limit, now) < limit.burst as f64 {
+                break;
+            }
+
+            self.by_age.remove(&(updated_at, key));
+            self.by_key.remove(&key);
+        }
+    }
+
+    fn drop_oldest(&mut self) {
+        if let Some(&(updated_at, key)) = self.by_age.iter().next() {
+            self.by_age.remove(&(updated_at, key));
+            self.by_key.remove(&key);
+        }
+    }
+}
+
+/// Outcome of taking a token from a bucket
+struct Decision {
+    allowed: bool,
+    limit: u32,
+    remaining: u32,
+    /// Seconds until the bucket is full again
+    reset: u64,
+    /// Seconds until the next request is allowed
+    retry_after: u64,
+}
+
+impl Decision {
+    fn new(allowed: bool, tokens: f64, limit: Limit) -> Self {
+        let seconds_until = |target: f64| {
+            let missing = (target - tokens).max(0.0);
+            (missing / limit.refill_per_second()).ceil() as u64
+        };
+
+        Decision {
+            allowed,
+            limit: limit.burst,
+            remaining: tokens.floor() as u32,
+            reset: seconds_until(limit.burst as f64),
+            retry_after: seconds_until(1.0),
+        }
+    }
+}
+
+/// In-memory token buckets per route group and client
+#[derive(Clone)]
+pub struct RateLimiter {
+    limits: RateLimits,
+    buckets: Arc<Mutex<Buckets>>,
+}
+
+impl RateLimiter {
+    pub fn new(limits: RateLimits) -> Self {
+        RateLimiter {
+            limits,
+            buckets: Arc::new(Mutex::new(Buckets::default())),
+        }
+    }
+
+    fn take(&self, group: RouteGroup, key: ClientKey) -> Decision {
+        let limit = self.limits.get(group);
+        let now = Instant::now();
+        let mut buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+        let buckets = &mut *buckets;
+
+        buckets.drop_full(&self.limits, now);
+
+        let key = (group, key);
+
+        if !buckets.by_key.contains_key(&key) && buckets.by_key.len() >= MAX_TRACKED_BUCKETS {
+            buckets.drop_oldest();
+        }
+
+        let bucket = buckets.by_key.entry(key).or_insert(Bucket {
+            tokens: limit.burst as f64,
+            updated_at: now,
+        });
+        buckets.by_age.remove(&(bucket.updated_at, key));
+        bucket.refill(limit, now);
+        buckets.by_age.insert((now, key));
+
+        let allowed = bucket.tokens >= 1.0;
+        if allowed {
+            bucket.tokens -= 1.0;
+        }
+
+        Decision::new(allowed, bucket.tokens, limit)
+    }
+
+    // Whether a request would be allowed, without taking a token
+    fn peek(&self, group: RouteGroup, key: ClientKey) -> Decision {
+        let limit = self.limits.get(group);
+        let buckets = self.buckets.lock().expect("rate limiter lock poisoned");
+
+        let tokens = match buckets.by_key.get(&(group, key)) {
+            Some(bucket) => bucket.tokens_at(limit, Instant::now()),
+            None => limit.burst as f64,
+        };
+
+        Decision::new(tokens >= 1.0, tokens, limit)
+    }
+}
+
+/// Middleware limiting the request rate per route group and token or client address
+pub async fn rate_limit<B: Send>(req: Request<B>, next: Next<B>) -> Response {
+    let group = RouteGroup::of(req.method(), req.uri().path());
+    let limiter = req
+        .extensions()
+        .get::<RateLimiter>()
+        .expect("rate limiter missing")
+        .clone();
+
+    let mut parts = RequestParts::new(req);
+
+    let ip_key = match ClientIp::from_request(&mut parts).await {
+        Ok(ClientIp(ip)) => ClientKey::of_ip(ip),
+        Err(err) => return err.into_response(),
+    };
+    let key = client_key(group, &parts).unwrap_or(ip_key);
+
+    let req = match parts.try_into_request() {
+        Ok(req) => req,
+        Err(_) => {
+            return AppError::ViolatedAssertion("Body extracted by rate limiter".to_string())
+                .into_response()
+        }
+    };
+
+    // Requests with invalid tokens are charged to the address, otherwise clients could get a
+    // new bucket for every request by sending random tokens
+    let decision = (key != ip_key)
+        .then(|| limiter.peek(group, ip_key))
+        .filter(|decision| !decision.allowed)
+        .unwrap_or_else(|| limiter.take(group, key));
+
+    let mut response = if decision.allowed {
+        next.run(req).await
+    } else {
+        AppError::RateLimited {
+            retry_after: decision.retry_after as i64,
+        }
+        .into_response()
+    };
+
+    if key != ip_key && response.status() == StatusCode::UNAUTHORIZED {
+        limiter.take(group, ip_key);
+    }
+
+    add_rate_limit_headers(response.headers_mut(), &decision);
+
+    response
+}
+
+// Limit authenticated API requests per token, so clients can't escape the limit by switching
+// addresses. The token isn't validated here, which would cost a database query per request.
+fn client_key<B>(group: RouteGroup, req: &RequestParts<B>) -> Option<ClientKey> {
+    if group != RouteGroup::Api {
+        return None;
+    }
+
+    let config = req
+        .extensions()
+        .get::<SharedConfig>()
+        .expect("config missing");
+
+    get_token_from_header(req.headers(), &config.cookie)
+        .ok()
+        .map(|token| ClientKey::of_token(&token))
+}
+
+fn add_rate_limit_headers(headers: &mut HeaderMap, decision: &Decision) {
+    headers.insert("ratelimit-limit", HeaderValue::from(decision.limit));
+    headers.insert("ratelimit-remaining", HeaderValue::from(decision.remaining));
+    headers.insert("ratelimit-reset", HeaderValue::from(decision.reset));
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::time::Duration;
+
+    const LIMIT: Limit = Limit {
+        burst: 10,
+        per_minute: 60,
+    };
+
+    fn limits() -> RateLimits {
+        RateLimits {
+            auth: LIMIT,
+            shares: LIMIT,
+            api: LIMIT,
+        }
+    }
+
+    fn insert(buckets: &mut Buckets, key: BucketKey, tokens: f64, updated_at: Instant) {
+        buckets.by_key.insert(key, Bucket { tokens, updated_at });
+        buckets.by_age.insert((updated_at, key));
+    }
+
+    fn ip_key(ip: &str) -> BucketKey {
+        (RouteGroup::Api, ClientKey::of_ip(ip.parse().unwrap()))
+    }
+
+    #[test]
+    fn route_groups() {
+        assert_eq!(RouteGroup::of(&Method::POST, "/session"), RouteGroup::Auth);
+        assert_eq!(
+            RouteGroup::of(&Method::PUT, "/user/reset"),
+            RouteGroup::Auth
+        );
+        assert_eq!(
+            RouteGroup::of(&Method::POST, "/user/recovery/reset"),
+            RouteGroup::Auth
+        );
+        assert_eq!(
+            RouteGroup::of(&Method::GET, "/shares/abc"),
+            RouteGroup::Shares
+        );
+        assert_eq!(
+            RouteGroup::of(&Method::POST, "/shares/abc"),
+            RouteGroup::Api
+        );
+        assert_eq!(RouteGroup::of(&Method::DELETE, "/session"), RouteGroup::Api);
+        assert_eq!(RouteGroup::of(&Method::GET, "/user"), RouteGroup::Api);
+    }
+
+    #[test]
+    fn bucket_refills_at_rate_up_to_burst() {
+        let start = Instant::now();
+        let mut bucket = Bucket {
+            tokens: 2.0,
+            updated_at: start,
+        };
+
+        assert_eq!(bucket.tokens_at(LIMIT, start + Duration::from_secs(3)), 5.0);
+        assert_eq!(
+            bucket.tokens_at(LIMIT, start + Duration::from_secs(60)),
+            10.0
+        );
+
+        bucket.refill(LIMIT, start + Duration::from_secs(4));
+        assert_eq!(bucket.tokens, 6.0);
+        assert_eq!(bucket.updated_at, start + Duration::from_secs(4));
+    }
+
+    #[test]
+    fn drop_full_stops_at_first_bucket_not_full() {
+        let start = Instant::now();
+        let mut buckets = Buckets::default();
+        insert(&mut buckets, ip_key("10.0.0.1"), 0.0, start);
+        insert(
+            &mut buckets,
+            ip_key("10.0.0.2"),
+            0.0,
+            start + Duration::from_secs(5),
+        );
+        insert(
+            &mut buckets,
+            ip_key("10.0.0.3"),
+            10.0,
+            start + Duration::from_secs(6),
+        );
+
+        buckets.drop_full(&limits(), start + Duration::from_secs(12));
+
+        assert!(!buckets.by_key.contains_key(&ip_key("10.0.0.1")));
+        assert!(buckets.by_key.contains_key(&ip_key("10.0.0.2")));
+        assert!(buckets.by_key.contains_key(&ip_key("10.0.0.3")));
+        assert_eq!(buckets.by_age.len(), 2);
+    }
+
+    #[test]
+    fn drop_oldest_removes_least_recently_updated() {
+        let start = Instant::now();
+        let mut buckets = Buckets::default();
+        insert(
+            &mut buckets,
+            ip_key("10.0.0.2"),
+            0.0,
+            start + Duration::from_secs(1),
+        );
+        insert(&mut buckets, ip_key("10.0.0.1"), 0.0, start);
+
+        buckets.drop_oldest();
+
+        assert!(!buckets.by_key.contains_key(&ip_key("10.0.0.1")));
+        assert!(buckets.by_key.contains_key(&ip_key("10.0.0.2")));
+        assert_eq!(buckets.by_age.len(), 1);
+    }
+
+    #[test]
+    fn ipv6_addresses_share_a_prefix() {
+        assert_eq!(ip_key("2001:db8::1"), ip_key("2001:db8::ffff:2"));
+        assert_ne!(ip_key("2001:db8::1"), ip_key("2001:db8:0:1::1"));
+        assert_eq!(ip_key("::ffff:10.0.0.1"), ip_key("10.0.0.1"));
+    }
+}
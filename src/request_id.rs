@@ -0,0 +1,84 @@
+use axum::{
+    extract::MatchedPath,
+    http::{HeaderValue, Request},
+    middleware::Next,
+    response::Response,
+};
+use std::time::Instant;
+use tracing::{field::Empty, info, info_span, Instrument, Span};
+
+use crate::util::get_request_id;
+
+/// Header carrying the id of a request, taken from the client or proxy if present
+pub const REQUEST_ID_HEADER: &str = "x-request-id";
+
+/// Maximum number of chars accepted in a propagated request id
+const MAX_REQUEST_ID_LENGTH: usize = 128;
+
+tokio::task_local! {
+    static REQUEST_ID: String;
+}
+
+/// Id of the request currently being handled, if any
+pub fn current_request_id() -> Option<String> {
+    REQUEST_ID.try_with(|id| id.clone()).ok()
+}
+
+/// Record the authenticated user on the span of the current request
+pub fn record_user(user_id: i32) {
+    Span::current().record("user_id", user_id);
+}
+
+/// Handle each request in its own span, identified by a propagated or generated request id
+/// which is echoed in the response
+pub async fn request_context<B>(req: Request<B>, next: Next<B>) -> Response {
+    let request_id = req
+        .headers()
+        .get(REQUEST_ID_HEADER)
+        .and_then(|value| value.to_str().ok())
+        .filter(|id| request_id_valid(id))
+        .map(str::to_string)
+        .unwrap_or_else(get_request_id);
+
+    let route = req
+        .extensions()
+        .get::<MatchedPath>()
+        .map(|path| path.as_str().to_string());
+
+    let span = info_span!(
+        "request",
+        request_id = %request_id,
+        method = %req.method(),
+        route = route.as_deref().unwrap_or("unmatched"),
+        user_id = Empty,
+    );
+
+    let start = Instant::now();
+
+    let mut response = REQUEST_ID
+        .scope(request_id.clone(), next.run(req).instrument(span.clone()))
+        .await;
+
+    span.in_scope(|| {
+        info!(
+            status = response.status().as_u16(),
+            latency_ms = start.elapsed().as_millis() as u64,
+            "Request handled"
+        )
+    });
+
+    if let Ok(value) = HeaderValue::from_str(&request_id) {
+        response.headers_mut().insert(REQUEST_ID_HEADER, value);
+    }
+
+    response
+}
+
+// Propagated ids end up in logs and headers, only allow a conservative set of chars
+fn request_id_valid(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LENGTH
+        && id
+            .chars()
+            .all(|c| c.is_ascii_alphanumeric() || c == '-' || c == '_' || c == '.')
+}
@@ -0,0 +1,46 @@
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use hyper::StatusCode;
+use sqlx::{query, PgPool};
+
+use crate::{authentication::AuthenticatedUser, error::AppError};
+
+/// Abort a key rotation, discarding all uploaded keys
+#[utoipa::path(
+    delete,
+    path = "/rotation",
+    tag = "rotation",
+    responses(
+        (status = 200, description = "Key rotation aborted"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "No active key rotation", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn abort_rotation_handler(
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    abort_rotation(user.user_id, &db).await?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn abort_rotation(user_id: i32, db: &PgPool) -> Result<(), AppError> {
+    let result = query!(
+        "DELETE
+        FROM key_rotations
+        WHERE user_id = $1;",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    if result.rows_affected() == 1 {
+        Ok(())
+    } else {
+        Err(AppError::NotFound("Rotation"))
+    }
+}
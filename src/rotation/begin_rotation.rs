@@ -0,0 +1,102 @@
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+use crate::{authentication::AuthenticatedUser, error::AppError};
+
+use super::{lock_notes, ROTATION_EXPIRATION_MINUTES, ROTATION_IN_PROGRESS};
+
+/// Response to begin rotation request
+#[derive(Serialize, ToSchema)]
+pub struct BeginRotationResponse {
+    expires_at: DateTime<Utc>,
+    notes: Vec<String>,
+}
+
+/// Begin a key rotation. Until it is committed or aborted, notes can't be written.
+/// Returns the tokens of all notes whose keys have to be re-wrapped.
+#[utoipa::path(
+    post,
+    path = "/rotation",
+    tag = "rotation",
+    responses(
+        (status = 200, description = "Key rotation begun", body = BeginRotationResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 409, description = "Key rotation already in progress", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn begin_rotation_handler(
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    let now = Utc::now();
+    let expires_at = now + Duration::minutes(ROTATION_EXPIRATION_MINUTES);
+
+    let notes = begin_rotation(user.user_id, now, expires_at, &db).await?;
+
+    Ok(Json(&BeginRotationResponse { expires_at, notes }).into_response())
+}
+
+async fn begin_rotation(
+    user_id: i32,
+    started_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<Vec<String>, AppError> {
+    let mut tx = db.begin().await?;
+
+    lock_notes(user_id, &mut tx).await?;
+
+    // Expired rotations are abandoned and can be replaced
+    let result = query!(
+        "INSERT INTO key_rotations (user_id, started_at, expires_at)
+        VALUES ($1, $2, $3)
+        ON CONFLICT (user_id) DO UPDATE
+        SET started_at = EXCLUDED.started_at, expires_at = EXCLUDED.expires_at
+        WHERE key_rotations.expires_at <= EXCLUDED.started_at
+        RETURNING id;",
+        user_id,
+        started_at,
+        expires_at,
+    )
+    .fetch_optional(&mut tx)
+    .await?;
+
+    let rotation_id = match result {
+        Some(row) => row.id,
+        None => {
+            tx.rollback().await?;
+            return Err(ROTATION_IN_PROGRESS);
+        }
+    };
+
+    // Drop keys uploaded to an abandoned rotation
+    query!(
+        "DELETE
+        FROM key_rotation_notes
+        WHERE rotation_id = $1;",
+        rotation_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let notes = query!(
+        "SELECT token
+        FROM notes
+        WHERE user_id = $1;",
+        user_id
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(notes.into_iter().map(|note| note.token).collect())
+}
@@ -0,0 +1,129 @@
+use axum::extract::Extension;
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use serde::Deserialize;
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Json};
+
+use super::lock_notes;
+
+/// Request to commit a key rotation
+#[derive(Deserialize, ToSchema)]
+pub struct CommitRotationRequest {
+    salt: String,
+}
+
+/// Commit the active key rotation. All note keys and the salt are replaced at
+/// once, or nothing is changed if any note is missing a re-wrapped key.
+#[utoipa::path(
+    post,
+    path = "/rotation/commit",
+    tag = "rotation",
+    request_body = CommitRotationRequest,
+    responses(
+        (status = 200, description = "Keys and salt replaced"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "No active key rotation", body = ErrorResponse),
+        (status = 422, description = "Notes are missing a re-wrapped key", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn commit_rotation_handler(
+    user: AuthenticatedUser,
+    Json(request): Json<CommitRotationRequest>,
+    db: Extension<PgPool>,
+) -> Result<StatusCode, AppError> {
+    let now = Utc::now();
+
+    let missing = commit_rotation(user.user_id, &request.salt, now, &db).await?;
+
+    if missing.is_empty() {
+        Ok(StatusCode::OK)
+    } else {
+        Err(AppError::KeysMissing(missing))
+    }
+}
+
+// Apply all uploaded keys and the new salt, returns tokens of notes without uploaded key
+async fn commit_rotation(
+    user_id: i32,
+    salt: &str,
+    now: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<Vec<String>, AppError> {
+    let mut tx = db.begin().await?;
+
+    lock_notes(user_id, &mut tx).await?;
+
+    let rotation_id = match query!(
+        "SELECT id
+        FROM key_rotations
+        WHERE user_id = $1 AND expires_at > $2
+        FOR UPDATE;",
+        user_id,
+        now
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    {
+        Some(row) => row.id,
+        None => {
+            tx.rollback().await?;
+            return Err(AppError::NotFound("Rotation"));
+        }
+    };
+
+    let missing = query!(
+        "SELECT notes.token
+        FROM notes
+        LEFT JOIN key_rotation_notes ON key_rotation_notes.note_id = notes.id
+            AND key_rotation_notes.rotation_id = $1
+        WHERE notes.user_id = $2 AND key_rotation_notes.note_id IS NULL;",
+        rotation_id,
+        user_id
+    )
+    .fetch_all(&mut tx)
+    .await?;
+
+    if !missing.is_empty() {
+        tx.rollback().await?;
+        return Ok(missing.into_iter().map(|note| note.token).collect());
+    }
+
+    // Bump modification date so other clients pick up the new keys
+    query!(
+        "UPDATE notes
+        SET key = key_rotation_notes.key, modified_at = $1
+        FROM key_rotation_notes
+        WHERE key_rotation_notes.note_id = notes.id AND key_rotation_notes.rotation_id = $2;",
+        now,
+        rotation_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "UPDATE users
+        SET salt = $1
+        WHERE id = $2;",
+        salt,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "DELETE
+        FROM key_rotations
+        WHERE id = $1;",
+        rotation_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(Vec::new())
+}
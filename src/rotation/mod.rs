@@ -0,0 +1,110 @@
+mod abort_rotation;
+mod begin_rotation;
+mod commit_rotation;
+mod upload_keys;
+
+pub use abort_rotation::abort_rotation_handler;
+pub use begin_rotation::begin_rotation_handler;
+pub use commit_rotation::commit_rotation_handler;
+pub use upload_keys::upload_keys_handler;
+
+use chrono::{DateTime, Utc};
+use sqlx::{query, Executor, Postgres, Transaction};
+use utoipa::OpenApi;
+
+use crate::error::AppError;
+
+/// OpenAPI document of the key rotation routes
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        begin_rotation::begin_rotation_handler,
+        abort_rotation::abort_rotation_handler,
+        upload_keys::upload_keys_handler,
+        commit_rotation::commit_rotation_handler,
+    ),
+    components(schemas(
+        begin_rotation::BeginRotationResponse,
+        upload_keys::RotatedKey,
+        commit_rotation::CommitRotationRequest,
+    ))
+)]
+pub struct RotationApi;
+
+/// Time a client has to upload all re-wrapped keys and commit: 1 hour
+const ROTATION_EXPIRATION_MINUTES: i64 = 60;
+
+/// Error of requests conflicting with an active key rotation
+const ROTATION_IN_PROGRESS: AppError = AppError::Conflict {
+    code: "rotation_in_progress",
+    message: "A key rotation is in progress",
+};
+
+/// Get id of the active key rotation of the user, if any
+async fn get_active_rotation<'c, E>(
+    user_id: i32,
+    now: DateTime<Utc>,
+    db: E,
+) -> Result<Option<i32>, AppError>
+where
+    E: Executor<'c, Database = Postgres>,
+{
+    let row = query!(
+        "SELECT id
+        FROM key_rotations
+        WHERE user_id = $1 AND expires_at > $2;",
+        user_id,
+        now
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| row.id))
+}
+
+/// Notes must not be written while their keys are being rotated. Notes have to be written in
+/// the same transaction, which blocks beginning or committing a rotation until it ends.
+pub async fn ensure_no_active_rotation(
+    user_id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), AppError> {
+    lock_rotation_state(user_id, tx).await?;
+
+    match get_active_rotation(user_id, Utc::now(), &mut *tx).await? {
+        Some(_) => Err(ROTATION_IN_PROGRESS),
+        None => Ok(()),
+    }
+}
+
+// Block beginning or committing a rotation until the transaction ends
+async fn lock_rotation_state(
+    user_id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(), AppError> {
+    query!(
+        "SELECT id
+        FROM users
+        WHERE id = $1
+        FOR SHARE;",
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    Ok(())
+}
+
+// Wait for note writes in progress and block new ones until the transaction ends
+async fn lock_notes(user_id: i32, tx: &mut Transaction<'_, Postgres>) -> Result<(), AppError> {
+    query!(
+        "SELECT id
+        FROM users
+        WHERE id = $1
+        FOR UPDATE;",
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    Ok(())
+}
@@ -0,0 +1,86 @@
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use chrono::Utc;
+use hyper::StatusCode;
+use serde::Deserialize;
+use sqlx::{query, PgPool};
+use std::collections::HashMap;
+use utoipa::ToSchema;
+
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Json};
+
+use super::{get_active_rotation, lock_rotation_state};
+
+/// Re-wrapped key of a single note
+#[derive(Deserialize, ToSchema)]
+pub struct RotatedKey {
+    id: String,
+    key: String,
+}
+
+/// Upload a batch of re-wrapped note keys to the active rotation. Keys uploaded
+/// earlier for the same note are replaced, as are earlier keys of the same batch.
+#[utoipa::path(
+    put,
+    path = "/rotation/keys",
+    tag = "rotation",
+    request_body = [RotatedKey],
+    responses(
+        (status = 200, description = "Keys stored"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "No active key rotation or unknown note", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn upload_keys_handler(
+    user: AuthenticatedUser,
+    Json(keys): Json<Vec<RotatedKey>>,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    upload_keys(user.user_id, keys, &db).await?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn upload_keys(user_id: i32, keys: Vec<RotatedKey>, db: &PgPool) -> Result<(), AppError> {
+    // A note listed more than once keeps its last key, like across batches
+    let keys: HashMap<String, String> = keys.into_iter().map(|key| (key.id, key.key)).collect();
+    let count = keys.len() as u64;
+    let (tokens, keys): (Vec<String>, Vec<String>) = keys.into_iter().unzip();
+
+    let mut tx = db.begin().await?;
+
+    // The rotation must not be committed while its keys are being uploaded
+    lock_rotation_state(user_id, &mut tx).await?;
+
+    let rotation_id = match get_active_rotation(user_id, Utc::now(), &mut tx).await? {
+        Some(rotation_id) => rotation_id,
+        None => return Err(AppError::NotFound("Rotation")),
+    };
+
+    let result = query!(
+        "INSERT INTO key_rotation_notes (rotation_id, note_id, key)
+        SELECT $1, notes.id, batch.key
+        FROM UNNEST($2::text[], $3::text[]) AS batch(token, key)
+        INNER JOIN notes ON notes.token = batch.token AND notes.user_id = $4
+        ON CONFLICT (rotation_id, note_id) DO UPDATE
+        SET key = EXCLUDED.key;",
+        rotation_id,
+        &tokens,
+        &keys,
+        user_id,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    // Reject the whole batch if it references unknown notes
+    if result.rows_affected() == count {
+        tx.commit().await?;
+        Ok(())
+    } else {
+        tx.rollback().await?;
+        Err(AppError::NotFound("Note"))
+    }
+}
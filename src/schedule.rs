@@ -1,83 +1,183 @@
-use crate::authentication::TOKEN_EXPIRATION_WEEKS;
+use crate::{
+    authentication::{SessionPolicy, FAILURE_RETENTION_HOURS, TOKEN_ROTATION_GRACE_SECONDS},
+    config::SharedConfig,
+    health::{Heartbeats, NOTES_DELETION, TOKENS_DELETION, USERS_DELETION},
+    metrics::Metrics,
+    shutdown::Shutdown,
+    users::{delete_all_user_data, AccountDeletionPolicy},
+};
 use chrono::{Duration, Utc};
-use log::{error, info};
 use sqlx::{query, PgPool};
-use tokio::time::{interval_at, Instant};
+use tokio::time::{interval_at, Instant, Interval};
+use tracing::{error, info};
 
-pub async fn notes_deletion_schedule(db: PgPool) {
-    let mut interval_timer = interval_at(
-        Instant::now() + Duration::minutes(7).to_std().unwrap(),
-        Duration::hours(7).to_std().unwrap(),
-    );
-    loop {
-        interval_timer.tick().await;
+pub async fn notes_deletion_schedule(
+    db: PgPool,
+    config: SharedConfig,
+    metrics: Metrics,
+    heartbeats: Heartbeats,
+    shutdown: Shutdown,
+) {
+    let mut interval_timer = schedule_timer(config.schedules.notes_deletion);
+    heartbeats.beat(NOTES_DELETION);
+    while next_run(&mut interval_timer, &shutdown).await {
+        heartbeats.beat(NOTES_DELETION);
 
-        let db_clone = db.clone();
+        let result = delete_expired_notes(&db, config.trash_retention).await;
+        record_deletion("notes", "expired notes", result, &metrics);
+    }
+}
+
+// Timer first firing after a sixtieth of the interval, so jobs don't run at startup
+fn schedule_timer(interval: Duration) -> Interval {
+    let interval = interval.to_std().expect("schedule interval negative");
 
-        tokio::spawn(async move {
-            delete_expired_notes(&db_clone).await;
-        });
+    interval_at(Instant::now() + interval / 60, interval)
+}
+
+// Wait for the next run of a job, returns false once shutdown is requested. Jobs run
+// inside the schedule, so a running job finishes before shutdown is noticed.
+async fn next_run(interval_timer: &mut Interval, shutdown: &Shutdown) -> bool {
+    tokio::select! {
+        _ = interval_timer.tick() => true,
+        _ = shutdown.clone().requested() => false,
     }
 }
 
-async fn delete_expired_notes(db: &PgPool) {
-    let one_month_ago = Utc::now() - Duration::weeks(4);
-    match query!(
+pub async fn delete_expired_notes(db: &PgPool, retention: Duration) -> Result<u64, sqlx::Error> {
+    let result = query!(
         "DELETE
         FROM notes 
         WHERE deleted_at IS NOT NULL AND deleted_at < $1;",
-        one_month_ago,
+        Utc::now() - retention,
     )
     .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+pub async fn users_deletion_schedule(
+    db: PgPool,
+    config: SharedConfig,
+    heartbeats: Heartbeats,
+    shutdown: Shutdown,
+) {
+    let mut interval_timer = schedule_timer(config.schedules.users_deletion);
+    heartbeats.beat(USERS_DELETION);
+    while next_run(&mut interval_timer, &shutdown).await {
+        heartbeats.beat(USERS_DELETION);
+
+        purge_deactivated_users(&db, &config.account_deletion).await;
+    }
+}
+
+async fn purge_deactivated_users(db: &PgPool, policy: &AccountDeletionPolicy) {
+    let users = match query!(
+        "SELECT id
+        FROM users
+        WHERE deleted_at < $1;",
+        Utc::now() - policy.grace_period,
+    )
+    .fetch_all(db)
     .await
     {
-        Ok(result) => {
-            info!(
-                "Deletion of expired notes with {} affected items",
-                result.rows_affected()
-            )
-        }
+        Ok(users) => users,
         Err(error) => {
-            error!("Deletion of expired notes caused error: {}", error)
+            error!("Loading deactivated users caused error: {}", error);
+            return;
         }
     };
-}
 
-pub async fn tokens_deletion_schedule(db: PgPool) {
-    let mut interval_timer = interval_at(
-        Instant::now() + Duration::minutes(3).to_std().unwrap(),
-        Duration::hours(3).to_std().unwrap(),
-    );
-    loop {
-        interval_timer.tick().await;
+    for user in users {
+        match delete_all_user_data(user.id, db).await {
+            Ok(_) => info!("Purged deactivated user {}", user.id),
+            Err(error) => error!("Purging user {} caused error: {}", user.id, error),
+        }
+    }
+}
 
-        let db_clone = db.clone();
+pub async fn tokens_deletion_schedule(
+    db: PgPool,
+    config: SharedConfig,
+    metrics: Metrics,
+    heartbeats: Heartbeats,
+    shutdown: Shutdown,
+) {
+    let mut interval_timer = schedule_timer(config.schedules.tokens_deletion);
+    heartbeats.beat(TOKENS_DELETION);
+    while next_run(&mut interval_timer, &shutdown).await {
+        heartbeats.beat(TOKENS_DELETION);
 
-        tokio::spawn(async move {
-            delete_expired_tokens(&db_clone).await;
-        });
+        let result = delete_expired_tokens(&db, &config.session).await;
+        record_deletion("auth_tokens", "expired auth tokens", result, &metrics);
+        log_deletion("expired API tokens", delete_expired_api_tokens(&db).await);
+        log_deletion(
+            "expired login failures",
+            delete_expired_login_failures(&db).await,
+        );
     }
 }
 
-async fn delete_expired_tokens(db: &PgPool) {
-    let token_expiration_period = Utc::now() - Duration::weeks(TOKEN_EXPIRATION_WEEKS);
-    match query!(
+pub async fn delete_expired_tokens(
+    db: &PgPool,
+    policy: &SessionPolicy,
+) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let result = query!(
         "DELETE
         FROM auth_tokens
-        WHERE created_at < $1;",
-        token_expiration_period,
+        WHERE session_started_at < $1 OR last_used_at < $2 OR rotated_at < $3;",
+        now - policy.lifetime,
+        now - policy.idle_timeout,
+        now - Duration::seconds(TOKEN_ROTATION_GRACE_SECONDS),
     )
     .execute(db)
-    .await
-    {
-        Ok(result) => {
-            info!(
-                "Deletion of expired auth tokens with {} affected items",
-                result.rows_affected()
-            )
-        }
-        Err(error) => {
-            error!("Deletion of expired auth tokens caused error: {}", error)
-        }
-    };
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn delete_expired_api_tokens(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let result = query!(
+        "DELETE
+        FROM api_tokens
+        WHERE expires_at < $1;",
+        Utc::now(),
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+async fn delete_expired_login_failures(db: &PgPool) -> Result<u64, sqlx::Error> {
+    let now = Utc::now();
+    let result = query!(
+        "DELETE
+        FROM login_failures
+        WHERE last_failure_at < $1 AND (locked_until IS NULL OR locked_until < $2);",
+        now - Duration::hours(FAILURE_RETENTION_HOURS),
+        now,
+    )
+    .execute(db)
+    .await?;
+
+    Ok(result.rows_affected())
+}
+
+fn log_deletion(items: &str, result: Result<u64, sqlx::Error>) {
+    match result {
+        Ok(count) => info!("Deletion of {} with {} affected items", items, count),
+        Err(error) => error!("Deletion of {} caused error: {}", items, error),
+    }
+}
+
+// Log the outcome of a deletion and record the affected rows under the job label
+fn record_deletion(job: &str, items: &str, result: Result<u64, sqlx::Error>, metrics: &Metrics) {
+    if let Ok(count) = result {
+        metrics.cleanup(job, count);
+    }
+
+    log_deletion(items, result);
 }
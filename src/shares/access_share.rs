@@ -1,16 +1,19 @@
-use crate::{error::AppError, shares::get_share_expiration, shares::KeyJson};
+use crate::{
+    error::AppError, extract::Path, metrics::Metrics, shares::get_share_expiration, shares::KeyJson,
+};
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     response::{IntoResponse, Response},
     Json,
 };
 use chrono::{DateTime, Utc};
-use log::error;
 use serde::Serialize;
 use sqlx::{query, PgPool};
+use tracing::error;
+use utoipa::ToSchema;
 
 /// Request to create share
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct AccessShareResponse {
     created_at: DateTime<Utc>,
     modified_at: DateTime<Utc>,
@@ -18,22 +21,47 @@ pub struct AccessShareResponse {
     iv: String,
 }
 
+#[utoipa::path(
+    get,
+    path = "/shares/{token}",
+    tag = "shares",
+    params(("token" = String, Path, description = "Token of the share")),
+    responses(
+        (status = 200, description = "Shared note, counts as a view", body = AccessShareResponse),
+        (status = 403, description = "Share has expired", body = ErrorResponse),
+        (status = 404, description = "Share doesn't exist", body = ErrorResponse),
+    ),
+)]
 pub async fn access_share_handler(
     Path(token): Path<String>,
     db: Extension<PgPool>,
+    Extension(metrics): Extension<Metrics>,
 ) -> Result<Response, AppError> {
-    let expires_at = get_share_expiration(&token, &db).await?;
+    let expires_at = match get_share_expiration(&token, &db).await {
+        Ok(expires_at) => expires_at,
+        Err(err @ AppError::NotFound(_)) => {
+            metrics.share_access("not_found");
+            return Err(err);
+        }
+        Err(err) => return Err(err),
+    };
 
     let now = Utc::now();
 
     if let Some(expires) = expires_at {
         if expires < now {
-            return Err(AppError::Unauthorized);
+            metrics.share_access("expired");
+            return Err(AppError::Forbidden {
+                code: "share_expired",
+                message: "Share has expired",
+            });
         }
     }
 
     let note = access_share(&token, &db).await?;
 
+    metrics.share_access("viewed");
+
     Ok(Json(&note).into_response())
 }
 
@@ -75,6 +103,6 @@ async fn access_share(token: &str, db: &PgPool) -> Result<AccessShareResponse, A
                 iv: key.iv_content,
             })
         }
-        None => Err(AppError::Unauthorized),
+        None => Err(AppError::NotFound("Share")),
     }
 }
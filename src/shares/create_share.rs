@@ -1,22 +1,28 @@
-use crate::{authentication::AuthenticatedFundedUser, error::AppError, util::get_share_token};
+use crate::{
+    authentication::AuthenticatedFundedUser,
+    error::AppError,
+    extract::Json,
+    util::{get_expires_at, get_share_token},
+};
 use axum::{
     extract::Extension,
     response::{IntoResponse, Response},
-    Json,
 };
-use chrono::{DateTime, Duration, Utc};
+use chrono::{DateTime, Utc};
 use serde::{Deserialize, Serialize};
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
 /// Request to create share
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct CreateShareRequest {
     note: String,
+    /// Hours until the share expires, at most 8760
     expires_in: Option<i64>,
 }
 
 /// Request to create share
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct CreateShareResponse {
     token: String,
     note: String,
@@ -25,6 +31,20 @@ pub struct CreateShareResponse {
 }
 
 /// Create a new share from an existing note
+#[utoipa::path(
+    post,
+    path = "/shares",
+    tag = "shares",
+    request_body = CreateShareRequest,
+    responses(
+        (status = 200, description = "Share created", body = CreateShareResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Note doesn't exist", body = ErrorResponse),
+        (status = 409, description = "Note is already shared", body = ErrorResponse),
+        (status = 422, description = "Expiration out of range", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["shares:manage"])),
+)]
 pub async fn create_share_handler(
     user: AuthenticatedFundedUser,
     Json(request): Json<CreateShareRequest>,
@@ -33,17 +53,13 @@ pub async fn create_share_handler(
     let now = Utc::now();
     let token = get_share_token();
 
-    let expires_at = {
-        let hours = request.expires_in;
-        if hours.is_none() {
-            None
-        } else {
-            Some(now + Duration::hours(hours.unwrap()))
-        }
-    };
+    let expires_at = get_expires_at(now, request.expires_in)?;
 
     if share_exists(&request.note, &db).await? {
-        return Err(AppError::Conflict);
+        return Err(AppError::Conflict {
+            code: "note_already_shared",
+            message: "Note is already shared",
+        });
     }
 
     create_share(&token, &request.note, user.user_id, now, expires_at, &db).await?;
@@ -82,7 +98,7 @@ async fn create_share(
     if row.rows_affected() == 1 {
         Ok(())
     } else {
-        Err(AppError::Unauthorized)
+        Err(AppError::NotFound("Note"))
     }
 }
 
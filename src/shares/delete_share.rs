@@ -1,12 +1,24 @@
 use axum::{
-    extract::{Extension, Path},
+    extract::Extension,
     response::{IntoResponse, Response},
 };
 use hyper::StatusCode;
 use sqlx::{query, PgPool};
 
-use crate::{authentication::AuthenticatedUser, error::AppError};
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Path};
 
+#[utoipa::path(
+    delete,
+    path = "/shares/{token}",
+    tag = "shares",
+    params(("token" = String, Path, description = "Token of the share")),
+    responses(
+        (status = 200, description = "Share deleted"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Share doesn't exist", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["shares:manage"])),
+)]
 pub async fn delete_share_handler(
     Path(token): Path<String>,
     user: AuthenticatedUser,
@@ -31,6 +43,6 @@ async fn delete_share(user_id: i32, token: &str, db: &PgPool) -> Result<(), AppE
     if row.rows_affected() == 1 {
         Ok(())
     } else {
-        Err(AppError::Unauthorized)
+        Err(AppError::NotFound("Share"))
     }
 }
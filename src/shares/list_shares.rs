@@ -7,11 +7,12 @@ use chrono::{DateTime, Utc};
 use serde::Serialize;
 use sqlx::{query, PgPool};
 use tokio_stream::StreamExt;
+use utoipa::ToSchema;
 
 use crate::{authentication::AuthenticatedUser, error::AppError};
 
 /// List shares response
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct ListShareResponse {
     token: String,
     note: String,
@@ -21,6 +22,16 @@ pub struct ListShareResponse {
 }
 
 /// List existing shares
+#[utoipa::path(
+    get,
+    path = "/shares",
+    tag = "shares",
+    responses(
+        (status = 200, description = "Shares of the user", body = [ListShareResponse]),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["shares:manage"])),
+)]
 pub async fn list_shares_handler(
     user: AuthenticatedUser,
     db: Extension<PgPool>,
@@ -11,26 +11,47 @@ pub use list_shares::list_shares_handler;
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use sqlx::{query, PgPool};
+use utoipa::OpenApi;
 
 use crate::error::AppError;
 
+/// OpenAPI document of the shares routes
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        create_share::create_share_handler,
+        list_shares::list_shares_handler,
+        delete_share::delete_share_handler,
+        access_share::access_share_handler,
+    ),
+    components(schemas(
+        create_share::CreateShareRequest,
+        create_share::CreateShareResponse,
+        list_shares::ListShareResponse,
+        access_share::AccessShareResponse,
+    ))
+)]
+pub struct SharesApi;
+
 /// Key type
 #[derive(Deserialize)]
 pub struct KeyJson {
     iv_content: String,
 }
 
+// Get expiration of share, shares of deactivated or disabled accounts don't exist
 async fn get_share_expiration(token: &str, db: &PgPool) -> Result<Option<DateTime<Utc>>, AppError> {
     match query!(
-        "SELECT expires_at
+        "SELECT shares.expires_at
         FROM shares 
-        WHERE token= $1",
+        INNER JOIN users ON users.id = shares.user_id
+        WHERE shares.token = $1 AND users.deleted_at IS NULL AND users.disabled_at IS NULL",
         token
     )
     .fetch_optional(db)
     .await?
     {
         Some(row) => Ok(row.expires_at),
-        None => Err(AppError::Unauthorized),
+        None => Err(AppError::NotFound("Share")),
     }
 }
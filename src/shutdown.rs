@@ -0,0 +1,66 @@
+use tokio::{signal, sync::watch};
+use tracing::{error, warn};
+
+/// Handle to the shutdown state of the server, cheap to clone
+#[derive(Clone)]
+pub struct Shutdown {
+    receiver: watch::Receiver<bool>,
+}
+
+impl Shutdown {
+    /// Start listening for SIGTERM and SIGINT
+    pub fn listen() -> Self {
+        let (sender, receiver) = watch::channel(false);
+
+        tokio::spawn(async move {
+            wait_for_signal().await;
+            warn!("Shutdown requested, draining requests and background jobs");
+
+            sender.send(true).ok();
+        });
+
+        Shutdown { receiver }
+    }
+
+    /// Whether shutdown was requested
+    pub fn is_requested(&self) -> bool {
+        *self.receiver.borrow()
+    }
+
+    /// Resolves once shutdown is requested
+    pub async fn requested(mut self) {
+        while !*self.receiver.borrow() {
+            if self.receiver.changed().await.is_err() {
+                return;
+            }
+        }
+    }
+}
+
+#[cfg(unix)]
+async fn wait_for_signal() {
+    let mut terminate = match signal::unix::signal(signal::unix::SignalKind::terminate()) {
+        Ok(terminate) => terminate,
+        Err(err) => {
+            error!("Listening for SIGTERM failed: {}", err);
+            return wait_for_interrupt().await;
+        }
+    };
+
+    tokio::select! {
+        _ = terminate.recv() => {}
+        _ = wait_for_interrupt() => {}
+    }
+}
+
+#[cfg(not(unix))]
+async fn wait_for_signal() {
+    wait_for_interrupt().await
+}
+
+async fn wait_for_interrupt() {
+    if let Err(err) = signal::ctrl_c().await {
+        error!("Listening for SIGINT failed: {}", err);
+        std::future::pending::<()>().await;
+    }
+}
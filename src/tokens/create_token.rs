@@ -0,0 +1,123 @@
+use crate::{
+    authentication::{AuthenticatedUser, Scope},
+    error::AppError,
+    extract::Json,
+    util::{get_api_token, get_expires_at, hash_token},
+};
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+/// Request to create API token
+#[derive(Deserialize, ToSchema)]
+pub struct CreateTokenRequest {
+    name: String,
+    scopes: Vec<Scope>,
+    /// Hours until the token expires, at most 8760
+    expires_in: Option<i64>,
+}
+
+/// Response to create API token, the only time the token itself is returned
+#[derive(Serialize, ToSchema)]
+pub struct CreateTokenResponse {
+    id: i32,
+    name: String,
+    token: String,
+    scopes: Vec<Scope>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+}
+
+/// Create a new personal API token
+#[utoipa::path(
+    post,
+    path = "/tokens",
+    tag = "tokens",
+    request_body = CreateTokenRequest,
+    responses(
+        (status = 200, description = "API token created, the token is only returned now", body = CreateTokenResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 422, description = "Name empty, no scopes or expiration out of range", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn create_token_handler(
+    user: AuthenticatedUser,
+    Json(request): Json<CreateTokenRequest>,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    if request.name.is_empty() {
+        return Err(AppError::Validation {
+            field: "name",
+            message: "Token name must not be empty".to_string(),
+        });
+    }
+
+    if request.scopes.is_empty() {
+        return Err(AppError::Validation {
+            field: "scopes",
+            message: "Token needs at least one scope".to_string(),
+        });
+    }
+
+    let now = Utc::now();
+    let token = get_api_token();
+    let expires_at = get_expires_at(now, request.expires_in)?;
+
+    let id = create_token(
+        user.user_id,
+        &request.name,
+        &hash_token(&token),
+        &request.scopes,
+        now,
+        expires_at,
+        &db,
+    )
+    .await?;
+
+    Ok(Json(&CreateTokenResponse {
+        id,
+        name: request.name,
+        token,
+        scopes: request.scopes,
+        created_at: now,
+        expires_at,
+    })
+    .into_response())
+}
+
+async fn create_token(
+    user_id: i32,
+    name: &str,
+    token_hash: &str,
+    scopes: &[Scope],
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    db: &PgPool,
+) -> Result<i32, AppError> {
+    let scopes: Vec<String> = scopes
+        .iter()
+        .map(|scope| scope.as_str().to_string())
+        .collect();
+
+    let row = query!(
+        "INSERT INTO api_tokens (user_id, name, token, scopes, created_at, expires_at)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        RETURNING id;",
+        user_id,
+        name,
+        token_hash,
+        &scopes,
+        created_at,
+        expires_at,
+    )
+    .fetch_one(db)
+    .await?;
+
+    Ok(row.id)
+}
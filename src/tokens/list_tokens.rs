@@ -0,0 +1,68 @@
+use crate::{
+    authentication::{AuthenticatedUser, Scope},
+    error::AppError,
+};
+use axum::{extract::Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+/// List API tokens response
+#[derive(Serialize, ToSchema)]
+pub struct ListTokenResponse {
+    id: i32,
+    name: String,
+    scopes: Vec<Scope>,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    last_used_at: Option<DateTime<Utc>>,
+}
+
+/// List existing API tokens of the user
+#[utoipa::path(
+    get,
+    path = "/tokens",
+    tag = "tokens",
+    responses(
+        (status = 200, description = "API tokens of the user", body = [ListTokenResponse]),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn list_tokens_handler(
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Json<Vec<ListTokenResponse>>, AppError> {
+    let tokens = list_tokens(user.user_id, &db).await?;
+
+    Ok(Json(tokens))
+}
+
+async fn list_tokens(user_id: i32, db: &PgPool) -> Result<Vec<ListTokenResponse>, AppError> {
+    let rows = query!(
+        "SELECT id, name, scopes, created_at, expires_at, last_used_at
+        FROM api_tokens
+        WHERE user_id = $1
+        ORDER BY created_at;",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|token| ListTokenResponse {
+            id: token.id,
+            name: token.name,
+            scopes: token
+                .scopes
+                .iter()
+                .filter_map(|name| Scope::from_name(name))
+                .collect(),
+            created_at: token.created_at,
+            expires_at: token.expires_at,
+            last_used_at: token.last_used_at,
+        })
+        .collect())
+}
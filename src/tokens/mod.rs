@@ -0,0 +1,26 @@
+mod create_token;
+mod list_tokens;
+mod revoke_token;
+
+pub use create_token::create_token_handler;
+pub use list_tokens::list_tokens_handler;
+pub use revoke_token::revoke_token_handler;
+
+use utoipa::OpenApi;
+
+/// OpenAPI document of the API token routes
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        create_token::create_token_handler,
+        list_tokens::list_tokens_handler,
+        revoke_token::revoke_token_handler,
+    ),
+    components(schemas(
+        crate::authentication::Scope,
+        create_token::CreateTokenRequest,
+        create_token::CreateTokenResponse,
+        list_tokens::ListTokenResponse,
+    ))
+)]
+pub struct TokensApi;
@@ -0,0 +1,49 @@
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use hyper::StatusCode;
+use sqlx::{query, PgPool};
+
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Path};
+
+/// Revoke an API token of the user
+#[utoipa::path(
+    delete,
+    path = "/tokens/{id}",
+    tag = "tokens",
+    params(("id" = i32, Path, description = "Id of the API token")),
+    responses(
+        (status = 200, description = "API token revoked"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "API token doesn't exist", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn revoke_token_handler(
+    Path(id): Path<i32>,
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    revoke_token(user.user_id, id, &db).await?;
+
+    Ok(StatusCode::OK.into_response())
+}
+
+async fn revoke_token(user_id: i32, id: i32, db: &PgPool) -> Result<(), AppError> {
+    let row = query!(
+        "DELETE
+        FROM api_tokens
+        WHERE id = $1 AND user_id = $2;",
+        id,
+        user_id,
+    )
+    .execute(db)
+    .await?;
+
+    if row.rows_affected() == 1 {
+        Ok(())
+    } else {
+        Err(AppError::NotFound("Token"))
+    }
+}
@@ -1,14 +1,21 @@
-use crate::{authentication::AuthenticatedUser, error::AppError, users::BCRYPT_COST};
-use axum::{extract::Extension, Json};
+use crate::{
+    authentication::{AuthenticatedUser, LoginThrottle},
+    client_ip::ClientIp,
+    config::SharedConfig,
+    error::AppError,
+    extract::Json,
+};
+use axum::extract::Extension;
 use bcrypt::hash;
 use hyper::StatusCode;
 use serde::Deserialize;
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
 use super::validate_user_with_credentials;
 
 /// This request form is expected for changing password
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct PasswordChangeRequest {
     name: String,
     password: String,
@@ -16,30 +23,41 @@ pub struct PasswordChangeRequest {
 }
 
 /// Change password of existing user
+#[utoipa::path(
+    put,
+    path = "/user",
+    tag = "users",
+    request_body = PasswordChangeRequest,
+    responses(
+        (status = 200, description = "Password changed"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
 pub async fn change_password_handler(
     Json(credentials): Json<PasswordChangeRequest>,
     user: AuthenticatedUser,
+    ClientIp(ip): ClientIp,
+    Extension(throttle): Extension<LoginThrottle>,
     db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
 ) -> Result<StatusCode, AppError> {
     if !validate_user_with_credentials(
         &user.username,
         user.user_id,
         &credentials.name,
         &credentials.password,
+        ip,
+        &throttle,
         &db,
     )
     .await?
     {
-        return Ok(StatusCode::UNAUTHORIZED);
+        return Err(AppError::InvalidCredentials);
     }
 
-    let hashed_password = hash(credentials.password_new, BCRYPT_COST);
-
-    if hashed_password.is_err() {
-        return Ok(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-
-    let hashed_password = hashed_password.unwrap();
+    let hashed_password = hash(credentials.password_new, config.bcrypt_cost)
+        .map_err(|err| AppError::ViolatedAssertion(format!("Hashing password failed: {}", err)))?;
 
     change_password(user.user_id, &hashed_password, &db).await?;
 
@@ -1,35 +1,140 @@
-use crate::{authentication::AuthenticatedUser, error::AppError, users::UserCredentials};
-use axum::{extract::Extension, Json};
-use hyper::StatusCode;
+use crate::{
+    authentication::{AuthenticatedUser, LoginThrottle},
+    client_ip::ClientIp,
+    config::SharedConfig,
+    error::AppError,
+    extract::Json,
+    users::UserCredentials,
+};
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Duration, Utc};
+use serde::Serialize;
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
 use super::validate_user_with_credentials;
 
-/// Delete user with all associated data
+/// Retention rules of deactivated accounts
+#[derive(Clone)]
+pub struct AccountDeletionPolicy {
+    /// Deactivated accounts are purged this long after deactivation
+    pub grace_period: Duration,
+}
+
+/// Response of deactivated accounts, stating when their data is purged
+#[derive(Serialize, Debug, ToSchema)]
+pub struct DeactivationResponse {
+    deleted_at: DateTime<Utc>,
+    purge_at: DateTime<Utc>,
+}
+
+impl DeactivationResponse {
+    pub fn new(deleted_at: DateTime<Utc>, policy: &AccountDeletionPolicy) -> Self {
+        DeactivationResponse {
+            deleted_at,
+            purge_at: deleted_at + policy.grace_period,
+        }
+    }
+}
+
+/// Deactivate user. Sessions and API tokens are revoked immediately, all data is purged
+/// after the grace period unless the user logs in again to reactivate the account.
+#[utoipa::path(
+    delete,
+    path = "/user",
+    tag = "users",
+    request_body = UserCredentials,
+    responses(
+        (status = 200, description = "Account deactivated", body = DeactivationResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
 pub async fn delete_user_handler(
     Json(credentials): Json<UserCredentials>,
     user: AuthenticatedUser,
+    ClientIp(ip): ClientIp,
+    Extension(throttle): Extension<LoginThrottle>,
     db: Extension<PgPool>,
-) -> Result<StatusCode, AppError> {
+    Extension(config): Extension<SharedConfig>,
+) -> Result<Response, AppError> {
     if !validate_user_with_credentials(
         &user.username,
         user.user_id,
         &credentials.name,
         &credentials.password,
+        ip,
+        &throttle,
         &db,
     )
     .await?
     {
-        return Ok(StatusCode::UNAUTHORIZED);
+        return Err(AppError::InvalidCredentials);
     }
 
-    delete_all_user_data(user.user_id, &db).await?;
+    let now = Utc::now();
+
+    deactivate_user(user.user_id, now, &db).await?;
 
-    Ok(StatusCode::OK)
+    Ok(Json(DeactivationResponse::new(now, &config.account_deletion)).into_response())
 }
 
-/// Delete all user data
-pub async fn delete_all_user_data(user_id: i32, db: &PgPool) -> Result<(), AppError> {
+// Mark user as deleted and revoke all its credentials
+async fn deactivate_user(user_id: i32, now: DateTime<Utc>, db: &PgPool) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+
+    query!(
+        "UPDATE users
+        SET deleted_at = $1
+        WHERE id = $2;",
+        now,
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "DELETE
+        FROM auth_tokens
+        WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "DELETE
+        FROM api_tokens
+        WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+/// Clear the deletion mark of a deactivated user
+pub async fn reactivate_user(user_id: i32, db: &PgPool) -> Result<(), AppError> {
+    query!(
+        "UPDATE users
+        SET deleted_at = NULL
+        WHERE id = $1;",
+        user_id
+    )
+    .execute(db)
+    .await?;
+
+    Ok(())
+}
+
+/// Delete all user data, returns whether the user existed
+pub async fn delete_all_user_data(user_id: i32, db: &PgPool) -> Result<bool, AppError> {
     let mut tx = db.begin().await?;
 
     query!(
@@ -41,6 +146,15 @@ pub async fn delete_all_user_data(user_id: i32, db: &PgPool) -> Result<(), AppEr
     .execute(&mut tx)
     .await?;
 
+    query!(
+        "DELETE
+        FROM key_rotations
+        WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
     query!(
         "DELETE
         FROM notes
@@ -60,6 +174,42 @@ pub async fn delete_all_user_data(user_id: i32, db: &PgPool) -> Result<(), AppEr
     .await?;
 
     query!(
+        "DELETE
+        FROM api_tokens
+        WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "DELETE
+        FROM password_resets
+        WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "DELETE
+        FROM invites
+        WHERE created_by = $1;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "DELETE
+        FROM recovery_material
+        WHERE user_id = $1;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    let result = query!(
         "DELETE
         FROM users
         WHERE id = $1;",
@@ -70,5 +220,5 @@ pub async fn delete_all_user_data(user_id: i32, db: &PgPool) -> Result<(), AppEr
 
     tx.commit().await?;
 
-    Ok(())
+    Ok(result.rows_affected() == 1)
 }
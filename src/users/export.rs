@@ -0,0 +1,276 @@
+use axum::{
+    body::StreamBody,
+    extract::Extension,
+    http::header::{CONTENT_DISPOSITION, CONTENT_TYPE},
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, PgPool, Postgres, Transaction};
+use std::io;
+use tokio::sync::mpsc::{channel, Sender};
+use tokio_stream::{wrappers::ReceiverStream, StreamExt};
+use tracing::{error, info};
+use utoipa::ToSchema;
+
+use crate::{authentication::AuthenticatedUser, error::AppError};
+
+/// Identifies export archives
+pub const EXPORT_FORMAT: &str = "fieldnotes-export";
+
+/// Version of the export format, bumped on incompatible changes
+pub const EXPORT_VERSION: i32 = 1;
+
+/// Number of lines buffered between the database and the client
+const EXPORT_BUFFER_LINES: usize = 32;
+
+/// Single line of an export archive. Archives are newline-delimited JSON starting with
+/// the header, followed by the profile and any number of notes, shares and sessions.
+#[derive(Serialize, Deserialize, ToSchema)]
+#[serde(tag = "type", rename_all = "snake_case")]
+pub enum ExportRecord {
+    Header {
+        format: String,
+        version: i32,
+        exported_at: DateTime<Utc>,
+    },
+    Profile {
+        username: String,
+        email: Option<String>,
+        salt: Option<String>,
+        created_at: DateTime<Utc>,
+    },
+    Note {
+        id: String,
+        created_at: DateTime<Utc>,
+        modified_at: DateTime<Utc>,
+        deleted_at: Option<DateTime<Utc>>,
+        metadata: String,
+        key: String,
+        content: String,
+    },
+    Share {
+        token: String,
+        note: String,
+        created_at: DateTime<Utc>,
+        expires_at: Option<DateTime<Utc>>,
+        view_count: i32,
+    },
+    Session {
+        created_at: DateTime<Utc>,
+        last_used_at: DateTime<Utc>,
+        user_agent: Option<String>,
+        device_name: Option<String>,
+    },
+}
+
+/// Stream all data of the user as newline-delimited JSON
+#[utoipa::path(
+    get,
+    path = "/user/export",
+    tag = "users",
+    responses(
+        (status = 200, description = "Archive with one record per line, starting with the header", body = ExportRecord, content_type = "application/x-ndjson"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn export_handler(user: AuthenticatedUser, db: Extension<PgPool>) -> Response {
+    let (sender, receiver) = channel(EXPORT_BUFFER_LINES);
+    let db = db.0.clone();
+
+    tokio::spawn(async move {
+        if let Err(err) = write_export(user.user_id, &db, &sender).await {
+            error!("Export of user {} failed: {}", user.user_id, err);
+            // Abort the response so the client doesn't mistake the archive for complete
+            let _ = sender
+                .send(Err(io::Error::from(io::ErrorKind::Interrupted)))
+                .await;
+        }
+    });
+
+    let filename = format!(
+        "attachment; filename=\"fieldnotes-{}.ndjson\"",
+        Utc::now().format("%Y-%m-%d")
+    );
+
+    (
+        [
+            (CONTENT_TYPE, "application/x-ndjson".to_string()),
+            (CONTENT_DISPOSITION, filename),
+        ],
+        StreamBody::new(ReceiverStream::new(receiver)),
+    )
+        .into_response()
+}
+
+// Write all records from a single snapshot of the database
+async fn write_export(
+    user_id: i32,
+    db: &PgPool,
+    sender: &Sender<Result<String, io::Error>>,
+) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+
+    query!("SET TRANSACTION ISOLATION LEVEL REPEATABLE READ, READ ONLY;")
+        .execute(&mut tx)
+        .await?;
+
+    emit(
+        sender,
+        ExportRecord::Header {
+            format: EXPORT_FORMAT.to_string(),
+            version: EXPORT_VERSION,
+            exported_at: Utc::now(),
+        },
+    )
+    .await?;
+
+    write_profile(user_id, &mut tx, sender).await?;
+    write_notes(user_id, &mut tx, sender).await?;
+    write_shares(user_id, &mut tx, sender).await?;
+    write_sessions(user_id, &mut tx, sender).await?;
+
+    tx.commit().await?;
+
+    info!("Exported data of user {}", user_id);
+
+    Ok(())
+}
+
+async fn write_profile(
+    user_id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+    sender: &Sender<Result<String, io::Error>>,
+) -> Result<(), AppError> {
+    let user = query!(
+        "SELECT username, email, salt, created_at
+        FROM users
+        WHERE id = $1;",
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    emit(
+        sender,
+        ExportRecord::Profile {
+            username: user.username,
+            email: user.email,
+            salt: user.salt,
+            created_at: user.created_at,
+        },
+    )
+    .await
+}
+
+async fn write_notes(
+    user_id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+    sender: &Sender<Result<String, io::Error>>,
+) -> Result<(), AppError> {
+    let mut rows = query!(
+        "SELECT token, created_at, modified_at, deleted_at, metadata, key, content
+        FROM notes
+        WHERE user_id = $1
+        ORDER BY id;",
+        user_id
+    )
+    .fetch(&mut *tx);
+
+    while let Some(note) = rows.try_next().await? {
+        emit(
+            sender,
+            ExportRecord::Note {
+                id: note.token,
+                created_at: note.created_at,
+                modified_at: note.modified_at,
+                deleted_at: note.deleted_at,
+                metadata: note.metadata,
+                key: note.key,
+                content: note.content,
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn write_shares(
+    user_id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+    sender: &Sender<Result<String, io::Error>>,
+) -> Result<(), AppError> {
+    let mut rows = query!(
+        "SELECT shares.token, notes.token AS note, shares.created_at, shares.expires_at,
+            shares.view_count
+        FROM shares
+        INNER JOIN notes ON notes.id = shares.note_id
+        WHERE shares.user_id = $1
+        ORDER BY shares.id;",
+        user_id
+    )
+    .fetch(&mut *tx);
+
+    while let Some(share) = rows.try_next().await? {
+        emit(
+            sender,
+            ExportRecord::Share {
+                token: share.token,
+                note: share.note,
+                created_at: share.created_at,
+                expires_at: share.expires_at,
+                view_count: share.view_count,
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+async fn write_sessions(
+    user_id: i32,
+    tx: &mut Transaction<'_, Postgres>,
+    sender: &Sender<Result<String, io::Error>>,
+) -> Result<(), AppError> {
+    let mut rows = query!(
+        "SELECT session_started_at, last_used_at, user_agent, device_name
+        FROM auth_tokens
+        WHERE user_id = $1 AND rotated_at IS NULL
+        ORDER BY id;",
+        user_id
+    )
+    .fetch(&mut *tx);
+
+    while let Some(session) = rows.try_next().await? {
+        emit(
+            sender,
+            ExportRecord::Session {
+                created_at: session.session_started_at,
+                last_used_at: session.last_used_at,
+                user_agent: session.user_agent,
+                device_name: session.device_name,
+            },
+        )
+        .await?;
+    }
+
+    Ok(())
+}
+
+// Send a single record as one line, fails if the client went away
+async fn emit(
+    sender: &Sender<Result<String, io::Error>>,
+    record: ExportRecord,
+) -> Result<(), AppError> {
+    let mut line = serde_json::to_string(&record)
+        .map_err(|err| AppError::ViolatedAssertion(format!("Export record malformed: {}", err)))?;
+    line.push('\n');
+
+    sender
+        .send(Ok(line))
+        .await
+        .map_err(|_| AppError::ViolatedAssertion("Export aborted by client".to_string()))
+}
@@ -0,0 +1,442 @@
+use axum::{
+    extract::{
+        rejection::{ContentLengthLimitRejection, StringRejection},
+        ContentLengthLimit, Extension,
+    },
+    response::{IntoResponse, Response},
+    Json,
+};
+use chrono::{DateTime, Utc};
+use serde::{Deserialize, Serialize};
+use sqlx::{query, PgPool, Postgres, Transaction};
+use std::collections::HashMap;
+use tracing::info;
+use utoipa::{IntoParams, ToSchema};
+
+use crate::{
+    authentication::AuthenticatedUser,
+    error::{rejection_message, AppError},
+    extract::Query,
+    rotation::ensure_no_active_rotation,
+    util::{get_note_token, get_share_token},
+};
+
+use super::export::{ExportRecord, EXPORT_FORMAT, EXPORT_VERSION};
+
+/// Maximum size of an import archive: 64 MiB
+const MAX_IMPORT_BYTES: u64 = 64 * 1024 * 1024;
+
+/// Options of an import, passed as query parameters
+#[derive(Deserialize, IntoParams)]
+#[into_params(parameter_in = Query)]
+pub struct ImportOptions {
+    #[serde(default)]
+    tokens: TokenMode,
+    #[serde(default)]
+    shares: bool,
+}
+
+/// How to assign tokens to imported notes and shares
+#[derive(Deserialize, Clone, Copy, PartialEq, Eq, Default, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum TokenMode {
+    /// Keep the tokens of the archive, falling back to fresh ones on collisions
+    #[default]
+    Preserve,
+    /// Always generate fresh tokens
+    Fresh,
+}
+
+/// Outcome of the import of a single note or share
+#[derive(Serialize, ToSchema)]
+pub struct ImportItemResult {
+    #[serde(rename = "type")]
+    kind: &'static str,
+    id: String,
+    new_id: Option<String>,
+    status: ImportStatus,
+    reason: Option<String>,
+}
+
+#[derive(Serialize, Clone, Copy, ToSchema)]
+#[serde(rename_all = "snake_case")]
+pub enum ImportStatus {
+    Created,
+    Renamed,
+    Skipped,
+}
+
+/// Response to import request
+#[derive(Serialize, ToSchema)]
+pub struct ImportResponse {
+    salt_updated: bool,
+    items: Vec<ImportItemResult>,
+}
+
+/// Import notes, salt and optionally shares from an export archive into the account
+/// of the user. Either everything is imported or nothing.
+#[utoipa::path(
+    post,
+    path = "/user/import",
+    tag = "users",
+    params(ImportOptions),
+    request_body(content = String, description = "Archive as produced by the export", content_type = "application/x-ndjson"),
+    responses(
+        (status = 200, description = "Archive imported", body = ImportResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 409, description = "Salt conflicts with existing notes or key rotation in progress", body = ErrorResponse),
+        (status = 413, description = "Archive too large", body = ErrorResponse),
+        (status = 422, description = "Archive invalid", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn import_handler(
+    user: AuthenticatedUser,
+    Query(options): Query<ImportOptions>,
+    archive: Result<
+        ContentLengthLimit<String, MAX_IMPORT_BYTES>,
+        ContentLengthLimitRejection<StringRejection>,
+    >,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    let archive = match archive {
+        Ok(ContentLengthLimit(archive)) => archive,
+        Err(ContentLengthLimitRejection::PayloadTooLarge(_)) => {
+            return Err(AppError::PayloadTooLarge {
+                limit: MAX_IMPORT_BYTES,
+            })
+        }
+        Err(rejection) => {
+            return Err(AppError::Validation {
+                field: "body",
+                message: rejection_message(&rejection),
+            })
+        }
+    };
+
+    let records = parse_archive(&archive)?;
+
+    let mut tx = db.begin().await?;
+
+    let salt = records.iter().find_map(|record| match record {
+        ExportRecord::Profile { salt, .. } => salt.as_deref(),
+        _ => None,
+    });
+
+    let salt_updated = match salt {
+        Some(salt) => match import_salt(user.user_id, salt, &mut tx).await? {
+            Some(updated) => updated,
+            None => {
+                tx.rollback().await?;
+                return Err(AppError::Conflict {
+                    code: "salt_mismatch",
+                    message: "Salt of archive differs from salt of account with existing notes",
+                });
+            }
+        },
+        None => false,
+    };
+
+    // Checked after the salt, which already locks the user for update, taking the shared lock
+    // first would deadlock concurrent imports upgrading it
+    ensure_no_active_rotation(user.user_id, &mut tx).await?;
+
+    let now = Utc::now();
+    let mut items = Vec::new();
+    // Note tokens of the archive mapped to ids of the imported notes
+    let mut notes = HashMap::new();
+
+    for record in &records {
+        if let ExportRecord::Note {
+            id,
+            created_at,
+            modified_at,
+            deleted_at,
+            metadata,
+            key,
+            content,
+        } = record
+        {
+            let note = NewNote {
+                created_at: *created_at,
+                modified_at: *modified_at,
+                deleted_at: *deleted_at,
+                metadata,
+                key,
+                content,
+            };
+            let (note_id, result) =
+                import_note(user.user_id, id, &note, options.tokens, &mut tx).await?;
+
+            notes.insert(id.as_str(), note_id);
+            items.push(result);
+        }
+    }
+
+    for record in &records {
+        if let ExportRecord::Share {
+            token,
+            note,
+            expires_at,
+            view_count,
+            ..
+        } = record
+        {
+            if !options.shares {
+                items.push(skipped("share", token, "Shares not requested"));
+                continue;
+            }
+
+            match notes.get(note.as_str()) {
+                Some(note_id) => {
+                    let share = NewShare {
+                        note_id: *note_id,
+                        created_at: now,
+                        expires_at: *expires_at,
+                        view_count: *view_count,
+                    };
+                    items.push(
+                        import_share(user.user_id, token, &share, options.tokens, &mut tx).await?,
+                    );
+                }
+                None => items.push(skipped("share", token, "Note not in archive")),
+            }
+        }
+    }
+
+    tx.commit().await?;
+
+    info!(
+        "Imported {} items into account of user {}",
+        items.len(),
+        user.user_id
+    );
+
+    Ok(Json(ImportResponse {
+        salt_updated,
+        items,
+    })
+    .into_response())
+}
+
+// Parse all lines of the archive, validating the header
+fn parse_archive(archive: &str) -> Result<Vec<ExportRecord>, AppError> {
+    let mut records = Vec::new();
+
+    for (index, line) in archive.lines().enumerate() {
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let record: ExportRecord =
+            serde_json::from_str(line).map_err(|err| archive_invalid(index, err))?;
+
+        let is_header = matches!(record, ExportRecord::Header { .. });
+        if records.is_empty() != is_header {
+            return Err(archive_invalid(
+                index,
+                "Archive has to start with a single header",
+            ));
+        }
+
+        if let ExportRecord::Header {
+            format, version, ..
+        } = &record
+        {
+            if format != EXPORT_FORMAT || *version != EXPORT_VERSION {
+                return Err(archive_invalid(
+                    index,
+                    format!("Unsupported archive format {} {}", format, version),
+                ));
+            }
+        }
+
+        records.push(record);
+    }
+
+    if records.is_empty() {
+        return Err(AppError::Validation {
+            field: "archive",
+            message: "Archive is empty".to_string(),
+        });
+    }
+
+    Ok(records)
+}
+
+// Rejection of the archive because of the line at `index`
+fn archive_invalid(index: usize, error: impl std::fmt::Display) -> AppError {
+    AppError::Validation {
+        field: "archive",
+        message: format!("Line {}: {}", index + 1, error),
+    }
+}
+
+// Adopt the salt of the archive. Returns whether the salt changed, or None if it
+// conflicts with the salt existing notes are encrypted with.
+async fn import_salt(
+    user_id: i32,
+    salt: &str,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<bool>, AppError> {
+    let row = query!(
+        r#"SELECT salt, EXISTS(SELECT 1 FROM notes WHERE user_id = $1) AS "has_notes!"
+        FROM users
+        WHERE id = $1
+        FOR UPDATE;"#,
+        user_id
+    )
+    .fetch_one(&mut *tx)
+    .await?;
+
+    match row.salt {
+        Some(existing) if existing == salt => Ok(Some(false)),
+        Some(_) if row.has_notes => Ok(None),
+        _ => {
+            query!(
+                "UPDATE users
+                SET salt = $1
+                WHERE id = $2;",
+                salt,
+                user_id
+            )
+            .execute(&mut *tx)
+            .await?;
+
+            Ok(Some(true))
+        }
+    }
+}
+
+struct NewNote<'a> {
+    created_at: DateTime<Utc>,
+    modified_at: DateTime<Utc>,
+    deleted_at: Option<DateTime<Utc>>,
+    metadata: &'a str,
+    key: &'a str,
+    content: &'a str,
+}
+
+async fn import_note(
+    user_id: i32,
+    token: &str,
+    note: &NewNote<'_>,
+    mode: TokenMode,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<(i32, ImportItemResult), AppError> {
+    if mode == TokenMode::Preserve {
+        if let Some(id) = insert_note(user_id, token, note, tx).await? {
+            return Ok((id, created("note", token, None)));
+        }
+    }
+
+    let new_token = get_note_token();
+
+    match insert_note(user_id, &new_token, note, tx).await? {
+        Some(id) => Ok((id, created("note", token, Some(new_token)))),
+        None => Err(AppError::ViolatedAssertion(
+            "Fresh note token collided".to_string(),
+        )),
+    }
+}
+
+// Insert note, returns None if the token is taken
+async fn insert_note(
+    user_id: i32,
+    token: &str,
+    note: &NewNote<'_>,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<Option<i32>, AppError> {
+    let row = query!(
+        "INSERT INTO notes (token, user_id, created_at, modified_at, deleted_at, metadata, key, content)
+        VALUES ($1, $2, $3, $4, $5, $6, $7, $8)
+        ON CONFLICT (token) DO NOTHING
+        RETURNING id;",
+        token,
+        user_id,
+        note.created_at,
+        note.modified_at,
+        note.deleted_at,
+        note.metadata,
+        note.key,
+        note.content,
+    )
+    .fetch_optional(&mut *tx)
+    .await?;
+
+    Ok(row.map(|row| row.id))
+}
+
+struct NewShare {
+    note_id: i32,
+    created_at: DateTime<Utc>,
+    expires_at: Option<DateTime<Utc>>,
+    view_count: i32,
+}
+
+async fn import_share(
+    user_id: i32,
+    token: &str,
+    share: &NewShare,
+    mode: TokenMode,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<ImportItemResult, AppError> {
+    if mode == TokenMode::Preserve && insert_share(user_id, token, share, tx).await? {
+        return Ok(created("share", token, None));
+    }
+
+    let new_token = get_share_token();
+
+    if insert_share(user_id, &new_token, share, tx).await? {
+        Ok(created("share", token, Some(new_token)))
+    } else {
+        Ok(skipped("share", token, "Note is already shared"))
+    }
+}
+
+// Insert share, returns false if the token is taken or the note already shared
+async fn insert_share(
+    user_id: i32,
+    token: &str,
+    share: &NewShare,
+    tx: &mut Transaction<'_, Postgres>,
+) -> Result<bool, AppError> {
+    let result = query!(
+        "INSERT INTO shares (token, note_id, user_id, created_at, expires_at, view_count)
+        VALUES ($1, $2, $3, $4, $5, $6)
+        ON CONFLICT DO NOTHING;",
+        token,
+        share.note_id,
+        user_id,
+        share.created_at,
+        share.expires_at,
+        share.view_count,
+    )
+    .execute(&mut *tx)
+    .await?;
+
+    Ok(result.rows_affected() == 1)
+}
+
+fn created(kind: &'static str, id: &str, new_id: Option<String>) -> ImportItemResult {
+    ImportItemResult {
+        kind,
+        id: id.to_string(),
+        status: match new_id {
+            Some(_) => ImportStatus::Renamed,
+            None => ImportStatus::Created,
+        },
+        new_id,
+        reason: None,
+    }
+}
+
+fn skipped(kind: &'static str, id: &str, reason: &str) -> ImportItemResult {
+    ImportItemResult {
+        kind,
+        id: id.to_string(),
+        new_id: None,
+        status: ImportStatus::Skipped,
+        reason: Some(reason.to_string()),
+    }
+}
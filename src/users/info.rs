@@ -2,9 +2,10 @@ use crate::{authentication::AuthenticatedUser, error::AppError, users::get_user_
 use axum::{extract::Extension, Json};
 use serde::Serialize;
 use sqlx::PgPool;
+use utoipa::ToSchema;
 
 /// Response to User info request
-#[derive(Serialize)]
+#[derive(Serialize, ToSchema)]
 pub struct UserInfoResponse {
     salt: Option<String>,
     username: String,
@@ -12,6 +13,16 @@ pub struct UserInfoResponse {
 }
 
 /// Get user info
+#[utoipa::path(
+    get,
+    path = "/user/info",
+    tag = "users",
+    responses(
+        (status = 200, description = "User info", body = UserInfoResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = []), ("api_token" = ["notes:read"])),
+)]
 pub async fn user_info_handler(
     user: AuthenticatedUser,
     db: Extension<PgPool>,
@@ -1,36 +1,53 @@
-use crate::authentication::{delete_all_auth_tokens, AuthenticatedUser};
+use crate::authentication::{delete_all_auth_tokens, AuthenticatedUser, LoginThrottle};
+use crate::client_ip::ClientIp;
+use crate::config::SharedConfig;
 use crate::error::AppError;
+use crate::extract::Json;
 use crate::users::UserCredentials;
 use crate::util::get_header_with_token;
 use axum::extract::Extension;
 use axum::response::{IntoResponse, Response};
-use axum::Json;
 use chrono::Duration;
-use hyper::StatusCode;
 use sqlx::PgPool;
 
 use super::validate_user_with_credentials;
 
 /// Delete all auth_token of user and override existing http-only cookies.
+#[utoipa::path(
+    delete,
+    path = "/allsessions",
+    tag = "users",
+    request_body = UserCredentials,
+    responses(
+        (status = 200, description = "All sessions revoked, clears the session cookie"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
 pub async fn invalidate_sessions(
     Json(credentials): Json<UserCredentials>,
     user: AuthenticatedUser,
+    ClientIp(ip): ClientIp,
+    Extension(throttle): Extension<LoginThrottle>,
     db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
 ) -> Result<Response, AppError> {
     if !validate_user_with_credentials(
         &user.username,
         user.user_id,
         &credentials.name,
         &credentials.password,
+        ip,
+        &throttle,
         &db,
     )
     .await?
     {
-        return Ok(StatusCode::UNAUTHORIZED.into_response());
+        return Err(AppError::InvalidCredentials);
     }
 
     delete_all_auth_tokens(user.user_id, &db).await?;
 
     // Set cookies empty and max-age 0 to force expiration
-    Ok(get_header_with_token("", Duration::zero()).into_response())
+    Ok(get_header_with_token("", Duration::zero(), &config.cookie).into_response())
 }
@@ -0,0 +1,65 @@
+use crate::{authentication::AuthenticatedUser, error::AppError};
+use axum::{extract::Extension, Json};
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+/// Response to list sessions request
+#[derive(Serialize, ToSchema)]
+pub struct ListSessionResponse {
+    id: i32,
+    created_at: DateTime<Utc>,
+    last_used_at: DateTime<Utc>,
+    user_agent: Option<String>,
+    device_name: Option<String>,
+    current: bool,
+}
+
+/// List all sessions of the user, flagging the one used for this request
+#[utoipa::path(
+    get,
+    path = "/sessions",
+    tag = "users",
+    responses(
+        (status = 200, description = "Sessions of the user", body = [ListSessionResponse]),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn list_sessions_handler(
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Json<Vec<ListSessionResponse>>, AppError> {
+    let sessions = list_sessions(user.user_id, user.session_id(), &db).await?;
+
+    Ok(Json(sessions))
+}
+
+async fn list_sessions(
+    user_id: i32,
+    current_session_id: Option<i32>,
+    db: &PgPool,
+) -> Result<Vec<ListSessionResponse>, AppError> {
+    let rows = query!(
+        "SELECT id, session_started_at, last_used_at, user_agent, device_name
+        FROM auth_tokens
+        WHERE user_id = $1 AND rotated_at IS NULL
+        ORDER BY last_used_at DESC;",
+        user_id
+    )
+    .fetch_all(db)
+    .await?;
+
+    Ok(rows
+        .into_iter()
+        .map(|session| ListSessionResponse {
+            id: session.id,
+            created_at: session.session_started_at,
+            last_used_at: session.last_used_at,
+            user_agent: session.user_agent,
+            device_name: session.device_name,
+            current: Some(session.id) == current_session_id,
+        })
+        .collect())
+}
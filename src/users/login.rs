@@ -1,40 +1,109 @@
-use crate::authentication::{store_auth_token, TOKEN_EXPIRATION_WEEKS};
+use crate::authentication::{store_auth_token, LoginThrottle};
+use crate::client_ip::ClientIp;
+use crate::config::SharedConfig;
 use crate::error::AppError;
-use crate::users::{get_password, user_exists_and_is_active, verify_password, UserCredentials};
+use crate::extract::Json;
+use crate::metrics::Metrics;
+use crate::users::{
+    get_password, user_exists_and_is_active, verify_password, DeactivationResponse, UserCredentials,
+};
 use crate::util::{get_auth_token, get_header_with_token};
 use axum::extract::Extension;
 use axum::response::{IntoResponse, Response};
-use axum::Json;
-use chrono::{Duration, Utc};
-use hyper::StatusCode;
+use chrono::Utc;
+use hyper::{header::USER_AGENT, HeaderMap};
 use sqlx::PgPool;
 
-use super::get_user_id;
+use super::{delete_user::reactivate_user, get_deactivation, get_user_id};
+
+/// Maximum number of chars stored for user agent and device name of a session
+const SESSION_LABEL_LENGTH: usize = 256;
 
 /// Log in existing user, this sets username and token cookies for future requests.
+/// Deactivated accounts have to confirm their reactivation within the grace period.
+#[utoipa::path(
+    post,
+    path = "/session",
+    tag = "users",
+    request_body = UserCredentials,
+    responses(
+        (status = 200, description = "Logged in, sets the session cookie"),
+        (status = 401, description = "Invalid credentials", body = ErrorResponse),
+        (status = 409, description = "Account is deactivated and has to be reactivated explicitly", body = ErrorResponse),
+        (status = 429, description = "Too many failed logins", body = ErrorResponse),
+    ),
+)]
 pub async fn login_handler(
     Json(user): Json<UserCredentials>,
+    headers: HeaderMap,
+    ClientIp(ip): ClientIp,
+    Extension(throttle): Extension<LoginThrottle>,
     db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
+    Extension(metrics): Extension<Metrics>,
 ) -> Result<Response, AppError> {
-    if !user_exists_and_is_active(&user.name, &db).await? {
-        return Ok(StatusCode::UNAUTHORIZED.into_response());
-    }
+    throttle.check(&user.name, ip, &db).await?;
+
+    let now = Utc::now();
+
+    let deactivated_at = if user_exists_and_is_active(&user.name, &db).await? {
+        None
+    } else {
+        match get_deactivation(&user.name, &db).await? {
+            Some(deleted_at) if deleted_at + config.account_deletion.grace_period > now => {
+                Some(deleted_at)
+            }
+            _ => {
+                throttle.record_failure(&user.name, ip, &db).await?;
+                metrics.auth_failure("login");
+                return Err(AppError::InvalidCredentials);
+            }
+        }
+    };
 
     let id = get_user_id(&user.name, &db).await?;
     let password = get_password(id, &db).await?;
 
-    match verify_password(&user.password, &password).await? {
-        false => return Ok(StatusCode::UNAUTHORIZED.into_response()),
-        true => (),
+    if !verify_password(&user.password, &password).await? {
+        throttle.record_failure(&user.name, ip, &db).await?;
+        metrics.auth_failure("login");
+        return Err(AppError::InvalidCredentials);
     }
 
-    let now = Utc::now();
+    throttle.record_success(&user.name, &db).await?;
+
+    if let Some(deleted_at) = deactivated_at {
+        if !user.reactivate {
+            let pending = DeactivationResponse::new(deleted_at, &config.account_deletion);
+            return Err(AppError::AccountDeactivated(pending));
+        }
+
+        reactivate_user(id, &db).await?;
+    }
 
     let token = get_auth_token();
 
-    store_auth_token(&user.name, &token, now, &db).await?;
+    let user_agent = headers
+        .get(USER_AGENT)
+        .and_then(|value| value.to_str().ok())
+        .map(truncate_session_label);
+    let device_name = user.device_name.as_deref().map(truncate_session_label);
 
-    let headers = get_header_with_token(&token, Duration::weeks(TOKEN_EXPIRATION_WEEKS));
+    store_auth_token(
+        &user.name,
+        &token,
+        now,
+        user_agent.as_deref(),
+        device_name.as_deref(),
+        &db,
+    )
+    .await?;
+
+    let headers = get_header_with_token(&token, config.session.lifetime, &config.cookie);
 
     Ok(headers.into_response())
 }
+
+fn truncate_session_label(label: &str) -> String {
+    label.chars().take(SESSION_LABEL_LENGTH).collect()
+}
@@ -1,5 +1,6 @@
 use crate::{
-    authentication::{delete_auth_token, AuthenticatedUser},
+    authentication::{delete_auth_token_by_id, AuthenticatedUser},
+    config::SharedConfig,
     error::AppError,
     util::get_header_with_token,
 };
@@ -11,12 +12,25 @@ use chrono::Duration;
 use sqlx::PgPool;
 
 /// Log out user. This deletes auth_token and overrides existing http-only cookies.
+#[utoipa::path(
+    delete,
+    path = "/session",
+    tag = "users",
+    responses(
+        (status = 200, description = "Logged out, clears the session cookie"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
 pub async fn logout_handler(
     user: AuthenticatedUser,
     db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
 ) -> Result<Response, AppError> {
-    delete_auth_token(&user.auth_token, &db).await?;
+    if let Some(session_id) = user.session_id() {
+        delete_auth_token_by_id(session_id, user.user_id, &db).await?;
+    }
 
     // Set cookies empty and max-age 0 to force expiration
-    Ok(get_header_with_token("", Duration::zero()).into_response())
+    Ok(get_header_with_token("", Duration::zero(), &config.cookie).into_response())
 }
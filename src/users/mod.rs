@@ -1,35 +1,102 @@
 mod change_password;
 mod delete_user;
+mod export;
+mod import;
 mod info;
 mod invalidate_sessions;
+mod list_sessions;
 mod login;
 mod logout;
+mod recovery;
+mod reset_password;
+mod revoke_session;
 mod salt;
 mod signup;
 
 pub use change_password::change_password_handler;
-pub use delete_user::delete_user_handler;
+pub use delete_user::{
+    delete_all_user_data, delete_user_handler, AccountDeletionPolicy, DeactivationResponse,
+};
+pub use export::export_handler;
+pub use import::import_handler;
 pub use info::user_info_handler;
 pub use invalidate_sessions::invalidate_sessions;
+pub use list_sessions::list_sessions_handler;
 pub use login::login_handler;
 pub use logout::logout_handler;
+pub use recovery::{
+    access_recovery_material_handler, get_recovery_material_handler,
+    store_recovery_material_handler,
+};
+pub use reset_password::{
+    confirm_password_reset_handler, request_password_reset_handler, PasswordResetConfig,
+};
+pub use revoke_session::revoke_session_handler;
 pub use salt::store_salt_handler;
-pub use signup::signup_handler;
+pub use signup::{signup_handler, store_user, SignupMode};
 
-use crate::error::AppError;
+use crate::{authentication::LoginThrottle, error::AppError};
 use bcrypt::verify;
-use log::error;
+use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use sqlx::{query, PgPool};
-
-/// Cost of bcrypt hashing algorithm
-const BCRYPT_COST: u32 = 12;
+use std::net::IpAddr;
+use tracing::error;
+use utoipa::{OpenApi, ToSchema};
+
+/// OpenAPI document of the account routes
+#[derive(OpenApi)]
+#[openapi(
+    paths(
+        signup::signup_handler,
+        login::login_handler,
+        delete_user::delete_user_handler,
+        change_password::change_password_handler,
+        logout::logout_handler,
+        info::user_info_handler,
+        salt::store_salt_handler,
+        export::export_handler,
+        import::import_handler,
+        reset_password::request_password_reset_handler,
+        reset_password::confirm_password_reset_handler,
+        recovery::store_recovery_material_handler,
+        recovery::get_recovery_material_handler,
+        recovery::access_recovery_material_handler,
+        invalidate_sessions::invalidate_sessions,
+        list_sessions::list_sessions_handler,
+        revoke_session::revoke_session_handler,
+    ),
+    components(schemas(
+        signup::SignupCredentials,
+        UserCredentials,
+        delete_user::DeactivationResponse,
+        change_password::PasswordChangeRequest,
+        info::UserInfoResponse,
+        salt::UserSaltRequest,
+        export::ExportRecord,
+        import::TokenMode,
+        import::ImportResponse,
+        import::ImportItemResult,
+        import::ImportStatus,
+        reset_password::PasswordResetRequest,
+        reset_password::PasswordResetConfirmation,
+        reset_password::PasswordResetResponse,
+        recovery::StoreRecoveryMaterialRequest,
+        recovery::AccessRecoveryMaterialRequest,
+        recovery::RecoveryMaterialResponse,
+        list_sessions::ListSessionResponse,
+    ))
+)]
+pub struct UsersApi;
 
 /// This request form is expected for login calls.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UserCredentials {
     name: String,
     password: String,
+    device_name: Option<String>,
+    #[serde(default)]
+    reactivate: bool,
 }
 
 pub struct UserInfo {
@@ -55,7 +122,7 @@ async fn get_user_info(user_id: i32, db: &PgPool) -> Result<UserInfo, AppError>
     })
 }
 
-async fn user_exists(name: &str, db: &PgPool) -> Result<bool, AppError> {
+pub async fn user_exists(name: &str, db: &PgPool) -> Result<bool, AppError> {
     let row = query!(
         "SELECT COUNT(id)
         FROM users 
@@ -72,17 +139,31 @@ async fn user_exists(name: &str, db: &PgPool) -> Result<bool, AppError> {
     }
 }
 
-fn username_valid(name: &str) -> bool {
+pub fn username_valid(name: &str) -> bool {
     let forbidden = ";/?:@&=+$,#*[]{}()^|";
 
     name.chars().all(|c| !forbidden.contains(c))
 }
 
+/// Get the deactivation date of a user pending deletion. Disabled users can't be reactivated.
+async fn get_deactivation(name: &str, db: &PgPool) -> Result<Option<DateTime<Utc>>, AppError> {
+    let row = query!(
+        "SELECT deleted_at
+        FROM users
+        WHERE username = $1 AND deleted_at IS NOT NULL AND disabled_at IS NULL;",
+        name
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.and_then(|row| row.deleted_at))
+}
+
 pub async fn user_exists_and_is_active(name: &str, db: &PgPool) -> Result<bool, AppError> {
     let row = query!(
         "SELECT COUNT(id)
         FROM users 
-        WHERE username = $1 AND deleted_at IS NULL;",
+        WHERE username = $1 AND deleted_at IS NULL AND disabled_at IS NULL;",
         name
     )
     .fetch_one(db)
@@ -95,17 +176,22 @@ pub async fn user_exists_and_is_active(name: &str, db: &PgPool) -> Result<bool,
     }
 }
 
+/// Check credentials of a logged in user. Failed attempts are throttled like logins.
 pub async fn validate_user_with_credentials(
     username: &str,
     user_id: i32,
     credential_name: &str,
     credential_password: &str,
+    ip: IpAddr,
+    throttle: &LoginThrottle,
     db: &PgPool,
 ) -> Result<bool, AppError> {
     if credential_name != username {
         return Ok(false);
     }
 
+    throttle.check(credential_name, ip, db).await?;
+
     if !user_exists_and_is_active(credential_name, db).await? {
         return Ok(false);
     }
@@ -113,9 +199,12 @@ pub async fn validate_user_with_credentials(
     let password = get_password(user_id, db).await?;
 
     if !verify_password(credential_password, &password).await? {
+        throttle.record_failure(credential_name, ip, db).await?;
         return Ok(false);
     }
 
+    throttle.record_success(credential_name, db).await?;
+
     Ok(true)
 }
 
@@ -0,0 +1,223 @@
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use chrono::{DateTime, Utc};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, PgPool};
+use utoipa::ToSchema;
+
+use super::{reset_password::RESET_TOKEN_INVALID, validate_user_with_credentials};
+use crate::{
+    authentication::{AuthenticatedUser, LoginThrottle},
+    client_ip::ClientIp,
+    error::AppError,
+    extract::Json,
+    util::hash_token,
+};
+
+/// This request form is expected for storing recovery material, the password is required as
+/// the previous material is replaced
+#[derive(Deserialize, ToSchema)]
+pub struct StoreRecoveryMaterialRequest {
+    name: String,
+    password: String,
+    version: i32,
+    material: String,
+}
+
+/// This request form is expected for accessing recovery material with a password reset token
+#[derive(Deserialize, ToSchema)]
+pub struct AccessRecoveryMaterialRequest {
+    token: String,
+}
+
+/// Response containing the client-encrypted recovery material
+#[derive(Serialize, ToSchema)]
+pub struct RecoveryMaterialResponse {
+    version: i32,
+    material: String,
+    created_at: DateTime<Utc>,
+}
+
+/// Store a new version of the recovery material. Versions have to be consecutive,
+/// the previous version is replaced.
+#[utoipa::path(
+    put,
+    path = "/user/recovery",
+    tag = "users",
+    request_body = StoreRecoveryMaterialRequest,
+    responses(
+        (status = 200, description = "Recovery material stored"),
+        (status = 401, description = "Not authenticated or wrong credentials", body = ErrorResponse),
+        (status = 409, description = "Version doesn't follow the current one", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn store_recovery_material_handler(
+    Json(request): Json<StoreRecoveryMaterialRequest>,
+    user: AuthenticatedUser,
+    ClientIp(ip): ClientIp,
+    Extension(throttle): Extension<LoginThrottle>,
+    db: Extension<PgPool>,
+) -> Result<StatusCode, AppError> {
+    if !validate_user_with_credentials(
+        &user.username,
+        user.user_id,
+        &request.name,
+        &request.password,
+        ip,
+        &throttle,
+        &db,
+    )
+    .await?
+    {
+        return Err(AppError::InvalidCredentials);
+    }
+
+    let now = Utc::now();
+
+    store_recovery_material(user.user_id, request.version, &request.material, now, &db).await?;
+
+    Ok(StatusCode::OK)
+}
+
+/// Get the current recovery material of the logged in user
+#[utoipa::path(
+    get,
+    path = "/user/recovery",
+    tag = "users",
+    responses(
+        (status = 200, description = "Current recovery material", body = RecoveryMaterialResponse),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "No recovery material stored", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn get_recovery_material_handler(
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    match get_recovery_material(user.user_id, &db).await? {
+        Some(material) => Ok(Json(&material).into_response()),
+        None => Err(AppError::NotFound("Recovery material")),
+    }
+}
+
+/// Get the current recovery material using a pending password reset token
+#[utoipa::path(
+    post,
+    path = "/user/recovery/reset",
+    tag = "users",
+    request_body = AccessRecoveryMaterialRequest,
+    responses(
+        (status = 200, description = "Current recovery material", body = RecoveryMaterialResponse),
+        (status = 403, description = "Reset token invalid or expired", body = ErrorResponse),
+        (status = 404, description = "No recovery material stored", body = ErrorResponse),
+    ),
+)]
+pub async fn access_recovery_material_handler(
+    Json(request): Json<AccessRecoveryMaterialRequest>,
+    db: Extension<PgPool>,
+) -> Result<Response, AppError> {
+    let now = Utc::now();
+
+    let user_id = match query!(
+        "SELECT password_resets.user_id
+        FROM password_resets
+        INNER JOIN users ON users.id = password_resets.user_id
+        WHERE password_resets.token = $1 AND password_resets.used_at IS NULL
+            AND password_resets.expires_at > $2 AND users.deleted_at IS NULL;",
+        hash_token(&request.token),
+        now,
+    )
+    .fetch_optional(&*db)
+    .await?
+    {
+        Some(row) => row.user_id,
+        None => return Err(RESET_TOKEN_INVALID),
+    };
+
+    match get_recovery_material(user_id, &db).await? {
+        Some(material) => Ok(Json(&material).into_response()),
+        None => Err(AppError::NotFound("Recovery material")),
+    }
+}
+
+async fn store_recovery_material(
+    user_id: i32,
+    version: i32,
+    material: &str,
+    created_at: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+
+    // Serialize concurrent rotations of the same user
+    query!(
+        "SELECT id
+        FROM users
+        WHERE id = $1
+        FOR UPDATE;",
+        user_id
+    )
+    .fetch_one(&mut tx)
+    .await?;
+
+    let result = query!(
+        "INSERT INTO recovery_material (user_id, version, material, created_at)
+        SELECT $1, $2, $3, $4
+        WHERE COALESCE((SELECT MAX(version) FROM recovery_material WHERE user_id = $1), 0) = $2 - 1;",
+        user_id,
+        version,
+        material,
+        created_at,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    if result.rows_affected() != 1 {
+        tx.rollback().await?;
+        return Err(AppError::Conflict {
+            code: "recovery_version_conflict",
+            message: "Recovery material version isn't the successor of the current one",
+        });
+    }
+
+    query!(
+        "DELETE
+        FROM recovery_material
+        WHERE user_id = $1 AND version < $2;",
+        user_id,
+        version,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+async fn get_recovery_material(
+    user_id: i32,
+    db: &PgPool,
+) -> Result<Option<RecoveryMaterialResponse>, AppError> {
+    let row = query!(
+        "SELECT version, material, created_at
+        FROM recovery_material
+        WHERE user_id = $1
+        ORDER BY version DESC
+        LIMIT 1;",
+        user_id
+    )
+    .fetch_optional(db)
+    .await?;
+
+    Ok(row.map(|row| RecoveryMaterialResponse {
+        version: row.version,
+        material: row.material,
+        created_at: row.created_at,
+    }))
+}
@@ -0,0 +1,268 @@
+use crate::{
+    authentication::{delete_all_api_tokens, delete_all_auth_tokens, LoginThrottle},
+    config::SharedConfig,
+    error::AppError,
+    extract::Json,
+    mailer::{Mail, SharedMailer},
+    util::{get_reset_token, hash_token},
+};
+use axum::extract::Extension;
+use bcrypt::hash;
+use chrono::{DateTime, Duration, Utc};
+use hyper::StatusCode;
+use serde::{Deserialize, Serialize};
+use sqlx::{query, PgPool};
+use tracing::error;
+use utoipa::ToSchema;
+
+/// Validity of password reset links: 1 hour
+const PASSWORD_RESET_EXPIRATION_MINUTES: i64 = 60;
+
+/// Warning about note keys derived from the old password
+const KEY_RECOVERY_WARNING: &str =
+    "Notes are encrypted with a key derived from your old password. \
+    They can only be decrypted again using your recovery material.";
+
+/// Error of reset tokens that are unknown, used or expired
+pub(super) const RESET_TOKEN_INVALID: AppError = AppError::Forbidden {
+    code: "reset_token_invalid",
+    message: "Reset token is invalid or expired",
+};
+
+/// Location of the password reset form in the write app
+#[derive(Clone)]
+pub struct PasswordResetConfig {
+    pub link_base: String,
+}
+
+/// This request form is expected for requesting a password reset
+#[derive(Deserialize, ToSchema)]
+pub struct PasswordResetRequest {
+    identifier: String,
+}
+
+/// This request form is expected for setting a new password with a reset token
+#[derive(Deserialize, ToSchema)]
+pub struct PasswordResetConfirmation {
+    token: String,
+    password_new: String,
+}
+
+/// Response to a successful password reset
+#[derive(Serialize, ToSchema)]
+pub struct PasswordResetResponse {
+    username: String,
+    warning: &'static str,
+}
+
+/// Request a password reset link by username or email. The response is the same
+/// whether or not a matching account exists.
+#[utoipa::path(
+    post,
+    path = "/user/reset",
+    tag = "users",
+    request_body = PasswordResetRequest,
+    responses((status = 200, description = "Reset link sent if a matching account exists")),
+)]
+pub async fn request_password_reset_handler(
+    Json(request): Json<PasswordResetRequest>,
+    db: Extension<PgPool>,
+    Extension(mailer): Extension<SharedMailer>,
+    Extension(config): Extension<SharedConfig>,
+) -> Result<StatusCode, AppError> {
+    let db = db.0.clone();
+
+    // Handled in the background so response timing doesn't reveal existing accounts
+    tokio::spawn(async move {
+        if let Err(err) =
+            send_password_reset(&request.identifier, &config.password_reset, &mailer, &db).await
+        {
+            error!("Password reset request failed: {:?}", err);
+        }
+    });
+
+    Ok(StatusCode::OK)
+}
+
+/// Set a new password with a reset token, log out all sessions and revoke all API tokens
+#[utoipa::path(
+    put,
+    path = "/user/reset",
+    tag = "users",
+    request_body = PasswordResetConfirmation,
+    responses(
+        (status = 200, description = "Password changed", body = PasswordResetResponse),
+        (status = 403, description = "Reset token invalid or expired", body = ErrorResponse),
+    ),
+)]
+pub async fn confirm_password_reset_handler(
+    Json(confirmation): Json<PasswordResetConfirmation>,
+    Extension(throttle): Extension<LoginThrottle>,
+    db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
+) -> Result<Json<PasswordResetResponse>, AppError> {
+    let hashed_password = match hash(confirmation.password_new, config.bcrypt_cost) {
+        Ok(hashed_password) => hashed_password,
+        Err(err) => {
+            error!("Error while hashing password: {:?}", err);
+            return Err(AppError::ViolatedAssertion("bcrypt hash error".to_string()));
+        }
+    };
+
+    let (user_id, username) = reset_password(
+        &hash_token(&confirmation.token),
+        &hashed_password,
+        Utc::now(),
+        &db,
+    )
+    .await?;
+
+    delete_all_auth_tokens(user_id, &db).await?;
+    delete_all_api_tokens(user_id, &db).await?;
+    // Lets the owner log in again right away if the account was locked
+    throttle.record_success(&username, &db).await?;
+
+    Ok(Json(PasswordResetResponse {
+        username,
+        warning: KEY_RECOVERY_WARNING,
+    }))
+}
+
+async fn send_password_reset(
+    identifier: &str,
+    config: &PasswordResetConfig,
+    mailer: &SharedMailer,
+    db: &PgPool,
+) -> Result<(), AppError> {
+    let users = query!(
+        "SELECT id, username, email AS \"email!\"
+        FROM users
+        WHERE (lower(username) = lower($1) OR lower(email) = lower($1))
+            AND email IS NOT NULL AND deleted_at IS NULL;",
+        identifier
+    )
+    .fetch_all(db)
+    .await?;
+
+    for user in users {
+        let token = get_reset_token();
+        let now = Utc::now();
+
+        store_password_reset(
+            user.id,
+            &hash_token(&token),
+            now,
+            now + Duration::minutes(PASSWORD_RESET_EXPIRATION_MINUTES),
+            db,
+        )
+        .await?;
+
+        let mail = Mail {
+            to: user.email,
+            subject: "Fieldnotes password reset".to_string(),
+            body: format!(
+                "Hi {},\n\n\
+                a password reset was requested for your account. Use the following link within \
+                {} minutes to choose a new password:\n\n{}?token={}\n\n\
+                {} All existing sessions will be logged out and API tokens revoked.\n\n\
+                If you didn't request this, you can ignore this mail.",
+                user.username,
+                PASSWORD_RESET_EXPIRATION_MINUTES,
+                config.link_base,
+                token,
+                KEY_RECOVERY_WARNING
+            ),
+        };
+
+        if let Err(err) = mailer.send(mail).await {
+            error!("Sending password reset mail failed: {:?}", err);
+        }
+    }
+
+    Ok(())
+}
+
+// Store reset token of user, replacing any unused previous ones
+async fn store_password_reset(
+    user_id: i32,
+    token_hash: &str,
+    created_at: DateTime<Utc>,
+    expires_at: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<(), AppError> {
+    let mut tx = db.begin().await?;
+
+    query!(
+        "DELETE
+        FROM password_resets
+        WHERE user_id = $1 AND used_at IS NULL;",
+        user_id
+    )
+    .execute(&mut tx)
+    .await?;
+
+    query!(
+        "INSERT INTO password_resets (user_id, token, created_at, expires_at)
+        VALUES ($1, $2, $3, $4);",
+        user_id,
+        token_hash,
+        created_at,
+        expires_at,
+    )
+    .execute(&mut tx)
+    .await?;
+
+    tx.commit().await?;
+
+    Ok(())
+}
+
+// Consume reset token and update password of its user
+async fn reset_password(
+    token_hash: &str,
+    password_hash: &str,
+    now: DateTime<Utc>,
+    db: &PgPool,
+) -> Result<(i32, String), AppError> {
+    let mut tx = db.begin().await?;
+
+    let user_id = match query!(
+        "UPDATE password_resets
+        SET used_at = $1
+        WHERE token = $2 AND used_at IS NULL AND expires_at > $1
+        RETURNING user_id;",
+        now,
+        token_hash,
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    {
+        Some(row) => row.user_id,
+        None => {
+            tx.rollback().await?;
+            return Err(RESET_TOKEN_INVALID);
+        }
+    };
+
+    let user = match query!(
+        "UPDATE users
+        SET password = $1
+        WHERE id = $2 AND deleted_at IS NULL
+        RETURNING username;",
+        password_hash,
+        user_id,
+    )
+    .fetch_optional(&mut tx)
+    .await?
+    {
+        Some(user) => user,
+        None => {
+            tx.rollback().await?;
+            return Err(RESET_TOKEN_INVALID);
+        }
+    };
+
+    tx.commit().await?;
+
+    Ok((user_id, user.username))
+}
@@ -0,0 +1,46 @@
+use crate::{
+    authentication::{delete_auth_token_by_id, AuthenticatedUser},
+    config::SharedConfig,
+    error::AppError,
+    extract::Path,
+    util::get_header_with_token,
+};
+use axum::{
+    extract::Extension,
+    response::{IntoResponse, Response},
+};
+use chrono::Duration;
+use hyper::StatusCode;
+use sqlx::PgPool;
+
+/// Revoke a single session of the user. Revoking the current session also
+/// overrides the http-only cookie.
+#[utoipa::path(
+    delete,
+    path = "/sessions/{id}",
+    tag = "users",
+    params(("id" = i32, Path, description = "Id of the session")),
+    responses(
+        (status = 200, description = "Session revoked, clears the session cookie if it is the current one"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 404, description = "Session doesn't exist", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
+pub async fn revoke_session_handler(
+    Path(session_id): Path<i32>,
+    user: AuthenticatedUser,
+    db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
+) -> Result<Response, AppError> {
+    if !delete_auth_token_by_id(session_id, user.user_id, &db).await? {
+        return Err(AppError::NotFound("Session"));
+    }
+
+    if Some(session_id) == user.session_id() {
+        // Set cookies empty and max-age 0 to force expiration
+        Ok(get_header_with_token("", Duration::zero(), &config.cookie).into_response())
+    } else {
+        Ok(StatusCode::OK.into_response())
+    }
+}
@@ -1,17 +1,30 @@
-use axum::{extract::Extension, Json};
+use axum::extract::Extension;
 use hyper::StatusCode;
 use serde::Deserialize;
 use sqlx::{query, PgPool};
+use utoipa::ToSchema;
 
-use crate::{authentication::AuthenticatedUser, error::AppError};
+use crate::{authentication::AuthenticatedUser, error::AppError, extract::Json};
 
 /// This request form is expected for storing salt
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct UserSaltRequest {
     salt: String,
 }
 
 /// Store user salt
+#[utoipa::path(
+    put,
+    path = "/user/salt",
+    tag = "users",
+    request_body = UserSaltRequest,
+    responses(
+        (status = 200, description = "Salt stored"),
+        (status = 401, description = "Not authenticated", body = ErrorResponse),
+        (status = 409, description = "Salt is already set", body = ErrorResponse),
+    ),
+    security(("session" = [])),
+)]
 pub async fn store_salt_handler(
     user: AuthenticatedUser,
     Json(salt): Json<UserSaltRequest>,
@@ -37,6 +50,9 @@ async fn store_salt(user_id: i32, salt: &str, db: &PgPool) -> Result<(), AppErro
     if result.rows_affected() == 1 {
         Ok(())
     } else {
-        Err(AppError::Conflict)
+        Err(AppError::Conflict {
+            code: "salt_already_set",
+            message: "Salt is already set",
+        })
     }
 }
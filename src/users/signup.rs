@@ -1,67 +1,148 @@
 use crate::{
-    error::AppError,
-    users::{user_exists, BCRYPT_COST},
+    authentication::USER_ROLE, config::SharedConfig, error::AppError, extract::Json,
+    invites::consume_invite, users::user_exists,
 };
+use axum::extract::Extension;
 use axum::http::StatusCode;
-use axum::{extract::Extension, Json};
 use bcrypt::hash;
 use chrono::{DateTime, Utc};
 use serde::Deserialize;
 use sqlx::{query, PgPool};
+use std::str::FromStr;
+use utoipa::ToSchema;
 
 use super::username_valid;
 
+/// Who is allowed to sign up
+#[derive(Clone, Copy, PartialEq, Eq)]
+pub enum SignupMode {
+    /// Anyone can sign up
+    Open,
+    /// Signing up requires a valid invite code
+    Invite,
+    /// No new accounts can be created
+    Closed,
+}
+
+impl FromStr for SignupMode {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Self, Self::Err> {
+        match name {
+            "open" => Ok(SignupMode::Open),
+            "invite" => Ok(SignupMode::Invite),
+            "closed" => Ok(SignupMode::Closed),
+            _ => Err(format!("unknown signup mode {}", name)),
+        }
+    }
+}
+
 /// This request form is expected for signupg calls.
-#[derive(Deserialize)]
+#[derive(Deserialize, ToSchema)]
 pub struct SignupCredentials {
     name: String,
     password: String,
     email: Option<String>,
+    invite: Option<String>,
 }
 
 /// Sign up new user. This stores the user data in the db.
+#[utoipa::path(
+    post,
+    path = "/user",
+    tag = "users",
+    request_body = SignupCredentials,
+    responses(
+        (status = 200, description = "User created"),
+        (status = 403, description = "Signup closed or invite missing or invalid", body = ErrorResponse),
+        (status = 409, description = "Username taken", body = ErrorResponse),
+        (status = 422, description = "Username invalid", body = ErrorResponse),
+    ),
+)]
 pub async fn signup_handler(
     Json(user): Json<SignupCredentials>,
     db: Extension<PgPool>,
+    Extension(config): Extension<SharedConfig>,
 ) -> Result<StatusCode, AppError> {
+    let invite = match (config.signup_mode, user.invite) {
+        (SignupMode::Open, _) => None,
+        (SignupMode::Invite, Some(invite)) => Some(invite),
+        (SignupMode::Invite, None) => {
+            return Err(AppError::Forbidden {
+                code: "invite_required",
+                message: "Signup requires an invite",
+            })
+        }
+        (SignupMode::Closed, _) => {
+            return Err(AppError::Forbidden {
+                code: "signup_closed",
+                message: "Signup is closed",
+            })
+        }
+    };
+
     if user_exists(&user.name, &db).await? {
-        return Ok(StatusCode::CONFLICT);
+        return Err(AppError::Conflict {
+            code: "username_taken",
+            message: "Username is already taken",
+        });
     }
 
     if !username_valid(&user.name) {
-        return Ok(StatusCode::UNPROCESSABLE_ENTITY);
+        return Err(AppError::Validation {
+            field: "name",
+            message: "Username contains invalid characters".to_string(),
+        });
     }
 
-    let hashed_password = hash(user.password, BCRYPT_COST);
-
-    if hashed_password.is_err() {
-        return Ok(StatusCode::INTERNAL_SERVER_ERROR);
-    }
-
-    let hashed_password = hashed_password.unwrap();
+    let hashed_password = hash(user.password, config.bcrypt_cost)
+        .map_err(|err| AppError::ViolatedAssertion(format!("Hashing password failed: {}", err)))?;
 
     let now = Utc::now();
 
-    store_user(&user.name, &hashed_password, user.email, now, &db).await?;
+    store_user(
+        &user.name,
+        &hashed_password,
+        user.email,
+        USER_ROLE,
+        invite.as_deref(),
+        now,
+        &db,
+    )
+    .await?;
 
     Ok(StatusCode::OK)
 }
 
-async fn store_user(
+/// Store a new user with the given role, consuming the invite if one is given
+pub async fn store_user(
     name: &str,
     password_hash: &str,
     email: Option<String>,
+    role: &str,
+    invite: Option<&str>,
     time: DateTime<Utc>,
     db: &PgPool,
 ) -> Result<(), AppError> {
     let mut tx = db.begin().await?;
 
+    if let Some(invite) = invite {
+        if !consume_invite(invite, time, &mut tx).await? {
+            tx.rollback().await?;
+            return Err(AppError::Forbidden {
+                code: "invite_invalid",
+                message: "Invite is invalid, used or expired",
+            });
+        }
+    }
+
     query!(
-        "INSERT INTO users (username, password, email, created_at)
-        VALUES ($1, $2, $3, $4);",
+        "INSERT INTO users (username, password, email, role, created_at)
+        VALUES ($1, $2, $3, $4, $5);",
         name,
         password_hash,
         email,
+        role,
         time,
     )
     .execute(&mut tx)
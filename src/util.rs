@@ -1,8 +1,9 @@
 use axum::http::{self, HeaderValue};
-use chrono::Duration;
+use chrono::{DateTime, Duration, Utc};
 use hyper::HeaderMap;
 use rand::distributions::Alphanumeric;
 use rand::Rng;
+use ring::digest::{digest, SHA256};
 
 use crate::error::AppError;
 
@@ -15,6 +16,24 @@ const SHARE_TOKEN_LENGTH: usize = 32;
 /// Number of alphanumeric chars in note tokens
 const NOTE_TOKEN_LENGTH: usize = 32;
 
+/// Number of alphanumeric chars in password reset tokens
+const RESET_TOKEN_LENGTH: usize = 64;
+
+/// Number of alphanumeric chars in invite codes
+const INVITE_CODE_LENGTH: usize = 24;
+
+/// Number of alphanumeric chars in generated request ids
+const REQUEST_ID_LENGTH: usize = 16;
+
+/// Number of alphanumeric chars in API tokens, excluding the prefix
+const API_TOKEN_LENGTH: usize = 48;
+
+/// Longest expiration in hours that can be requested, one year
+const MAX_EXPIRES_IN_HOURS: i64 = 8760;
+
+/// Prefix of API tokens, distinguishes them from session tokens
+pub const API_TOKEN_PREFIX: &str = "fnp_";
+
 /// Get a secure token for session tokens
 pub fn get_auth_token() -> String {
     rand::rngs::OsRng
@@ -42,38 +61,111 @@ pub fn get_note_token() -> String {
         .collect::<String>()
 }
 
-pub fn truncate_auth_token(token: &str) -> String {
-    let length = token.len();
-    let beginning = &token[..6];
-    let end = &token[(length - 6)..];
+/// Get a secure code for signup invites
+pub fn get_invite_code() -> String {
+    rand::rngs::OsRng
+        .sample_iter(&Alphanumeric)
+        .take(INVITE_CODE_LENGTH)
+        .map(char::from)
+        .collect::<String>()
+}
 
-    return format!("{}..{}", beginning, end);
+/// Get an id for requests arriving without one
+pub fn get_request_id() -> String {
+    rand::thread_rng()
+        .sample_iter(&Alphanumeric)
+        .take(REQUEST_ID_LENGTH)
+        .map(char::from)
+        .collect::<String>()
 }
 
-pub fn get_token_from_header(headers: &HeaderMap) -> Result<String, AppError> {
-    if let Some(cookie) = headers
-        .get(http::header::COOKIE)
-        .and_then(|value| value.to_str().ok())
-        .map(|value| value.to_string())
-    {
-        let mut split = cookie.split('=');
-        split.next();
-        match split.next() {
-            Some(str) => Ok(str.into()),
-            None => Err(AppError::Unauthorized),
-        }
-    } else {
-        Err(AppError::Unauthorized)
+/// Get a secure token for password reset links
+pub fn get_reset_token() -> String {
+    rand::rngs::OsRng
+        .sample_iter(&Alphanumeric)
+        .take(RESET_TOKEN_LENGTH)
+        .map(char::from)
+        .collect::<String>()
+}
+
+/// Get a secure token for personal API tokens
+pub fn get_api_token() -> String {
+    let token = rand::rngs::OsRng
+        .sample_iter(&Alphanumeric)
+        .take(API_TOKEN_LENGTH)
+        .map(char::from)
+        .collect::<String>();
+
+    format!("{}{}", API_TOKEN_PREFIX, token)
+}
+
+/// Hex encoded SHA-256 digest of a token, used to store secrets at rest
+pub fn hash_token(token: &str) -> String {
+    digest(&SHA256, token.as_bytes())
+        .as_ref()
+        .iter()
+        .map(|byte| format!("{:02x}", byte))
+        .collect()
+}
+
+/// Attributes of the session token cookie
+#[derive(Clone)]
+pub struct CookieConfig {
+    pub name: String,
+    pub domain: Option<String>,
+    pub path: String,
+}
+
+/// Get session token from `Authorization: Bearer` header or, if absent, from the
+/// session cookie
+pub fn get_token_from_header(
+    headers: &HeaderMap,
+    config: &CookieConfig,
+) -> Result<String, AppError> {
+    if let Some(token) = get_bearer_token(headers) {
+        return Ok(token);
     }
+
+    get_cookie(headers, &config.name).ok_or(AppError::Unauthorized)
 }
 
-pub fn get_header_with_token(token: &str, duration: Duration) -> HeaderMap {
-    let cookie = format!(
-        "token={};HttpOnly;Secure;SameSite=Strict;Max-Age={}",
+/// Find cookie by name across all cookie headers
+pub fn get_cookie(headers: &HeaderMap, name: &str) -> Option<String> {
+    headers
+        .get_all(http::header::COOKIE)
+        .iter()
+        .filter_map(|value| value.to_str().ok())
+        .flat_map(|value| value.split(';'))
+        .filter_map(|pair| pair.trim().split_once('='))
+        .find(|(key, _)| key.trim() == name)
+        .map(|(_, value)| value.trim().trim_matches('"').to_string())
+        .filter(|value| !value.is_empty())
+}
+
+/// Get token from `Authorization: Bearer` header
+pub fn get_bearer_token(headers: &HeaderMap) -> Option<String> {
+    headers
+        .get(http::header::AUTHORIZATION)
+        .and_then(|value| value.to_str().ok())
+        .and_then(|value| value.trim().split_once(' '))
+        .filter(|(scheme, _)| scheme.eq_ignore_ascii_case("Bearer"))
+        .map(|(_, token)| token.trim().to_string())
+        .filter(|token| !token.is_empty())
+}
+
+pub fn get_header_with_token(token: &str, duration: Duration, config: &CookieConfig) -> HeaderMap {
+    let mut cookie = format!(
+        "{}={};HttpOnly;Secure;SameSite=Strict;Path={};Max-Age={}",
+        config.name,
         token,
+        config.path,
         duration.num_seconds()
     );
 
+    if let Some(domain) = &config.domain {
+        cookie.push_str(&format!(";Domain={}", domain));
+    }
+
     let cookie_header = HeaderValue::from_str(&cookie).expect("Cookie value invalid");
 
     let mut headers = HeaderMap::new();
@@ -81,3 +173,100 @@ pub fn get_header_with_token(token: &str, duration: Duration) -> HeaderMap {
 
     headers
 }
+
+/// Get expiration date `expires_in` hours from now, if an expiration was requested
+pub fn get_expires_at(
+    now: DateTime<Utc>,
+    expires_in: Option<i64>,
+) -> Result<Option<DateTime<Utc>>, AppError> {
+    let hours = match expires_in {
+        Some(hours) => hours,
+        None => return Ok(None),
+    };
+
+    let expires_at = Some(hours)
+        .filter(|hours| (1..=MAX_EXPIRES_IN_HOURS).contains(hours))
+        .and_then(|hours| now.checked_add_signed(Duration::hours(hours)));
+
+    match expires_at {
+        Some(expires_at) => Ok(Some(expires_at)),
+        None => Err(AppError::Validation {
+            field: "expires_in",
+            message: format!(
+                "Expiration must be between 1 and {} hours",
+                MAX_EXPIRES_IN_HOURS
+            ),
+        }),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use axum::http::{header, HeaderMap, HeaderValue};
+
+    use super::{get_bearer_token, get_cookie};
+
+    fn headers(entries: &[(header::HeaderName, &str)]) -> HeaderMap {
+        let mut headers = HeaderMap::new();
+        for (name, value) in entries {
+            headers.append(name, HeaderValue::from_str(value).unwrap());
+        }
+        headers
+    }
+
+    #[test]
+    fn cookie_among_several_in_one_header() {
+        let headers = headers(&[(header::COOKIE, "theme=dark; token=abc; lang=en")]);
+
+        assert_eq!(get_cookie(&headers, "token").as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn cookie_in_second_cookie_header() {
+        let headers = headers(&[
+            (header::COOKIE, "theme=dark"),
+            (header::COOKIE, "token=abc"),
+        ]);
+
+        assert_eq!(get_cookie(&headers, "token").as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn cookie_with_quoted_value() {
+        let headers = headers(&[(header::COOKIE, "token=\"abc\"")]);
+
+        assert_eq!(get_cookie(&headers, "token").as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn cookie_with_similar_name_ignored() {
+        let similar = headers(&[(header::COOKIE, "xtoken=evil; token_old=stale")]);
+        let both = headers(&[(header::COOKIE, "xtoken=evil; token=abc")]);
+
+        assert_eq!(get_cookie(&similar, "token"), None);
+        assert_eq!(get_cookie(&both, "token").as_deref(), Some("abc"));
+    }
+
+    #[test]
+    fn empty_cookie_ignored() {
+        let headers = headers(&[(header::COOKIE, "token=; theme=dark")]);
+
+        assert_eq!(get_cookie(&headers, "token"), None);
+    }
+
+    #[test]
+    fn bearer_scheme_case_insensitive() {
+        let headers = headers(&[(header::AUTHORIZATION, "bearer fnp_abc")]);
+
+        assert_eq!(get_bearer_token(&headers).as_deref(), Some("fnp_abc"));
+    }
+
+    #[test]
+    fn other_schemes_and_empty_tokens_ignored() {
+        let basic = headers(&[(header::AUTHORIZATION, "Basic YWxpY2U6c2VjcmV0")]);
+        let empty = headers(&[(header::AUTHORIZATION, "Bearer  ")]);
+
+        assert_eq!(get_bearer_token(&basic), None);
+        assert_eq!(get_bearer_token(&empty), None);
+    }
+}